@@ -0,0 +1,54 @@
+//! Link the BLAS and LAPACK that R uses when the `linalg` feature is enabled.
+//!
+//! R can be built against its bundled `Rblas`/`Rlapack` or an external BLAS (e.g. Debian's `libblas`),
+//! so the libraries are taken from `R CMD config` instead of being hard-coded.
+use std::{env, path::PathBuf, process::Command};
+
+/// The linker flags R was configured with for `var`, e.g. `-L/usr/lib/R/lib -lRblas`.
+fn r_config(var: &str) -> Option<String> {
+    let r = match env::var_os("R_HOME") {
+        Some(home) => PathBuf::from(home).join("bin").join("R"),
+        None => PathBuf::from("R"),
+    };
+    let output = Command::new(r).args(["CMD", "config", var]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8(output.stdout).ok()?.trim().to_string())
+}
+
+/// Emit link directives for linker flags in the `-L<dir> -l<lib>` form.
+fn link_flags(flags: &str) {
+    for flag in flags.split_whitespace() {
+        if let Some(dir) = flag.strip_prefix("-L") {
+            println!("cargo:rustc-link-search=native={}", dir);
+        } else if let Some(lib) = flag.strip_prefix("-l") {
+            println!("cargo:rustc-link-lib=dylib={}", lib);
+        }
+    }
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed=R_HOME");
+    println!("cargo:rerun-if-env-changed=R_BLAS_LIBS");
+    println!("cargo:rerun-if-env-changed=R_LAPACK_LIBS");
+
+    if env::var_os("CARGO_FEATURE_LINALG").is_none() {
+        return;
+    }
+
+    for (override_var, config_var) in [
+        ("R_BLAS_LIBS", "BLAS_LIBS"),
+        ("R_LAPACK_LIBS", "LAPACK_LIBS"),
+    ] {
+        match env::var(override_var).ok().or_else(|| r_config(config_var)) {
+            Some(flags) => link_flags(&flags),
+            None => println!(
+                "cargo:warning=could not run `R CMD config {}`, set {} to link BLAS/LAPACK explicitly; \
+                 otherwise the symbols are resolved from the R process at load time",
+                config_var, override_var
+            ),
+        }
+    }
+}
@@ -1,15 +1,12 @@
 use typed_sexp::{
     prelude::*,
-    sexp::{
-        env::{Env, Symbol},
-        function::Callable,
-        matrix::Matrix,
-        vector::RealVector,
-    },
+    sexp::{env::Env, function::Callable, matrix::Matrix, vector::RealVector},
+    thread::MainThread,
 };
 
 #[export_name = "vector_add"]
 pub extern "C" fn vector_add(a: SEXP, b: SEXP) -> SEXP {
+    MainThread::register();
     let a = a
         .coerce(SEXPTYPE::REALSXP)
         .downcast_to::<RealVector<_>>()
@@ -34,6 +31,7 @@ pub extern "C" fn vector_add(a: SEXP, b: SEXP) -> SEXP {
 
 #[export_name = "matrix_multiply"]
 pub extern "C" fn matrix_multiply(mat: SEXP, vec: SEXP) -> SEXP {
+    MainThread::register();
     let mat = mat
         .coerce(SEXPTYPE::REALSXP)
         .downcast_to::<RealVector<_>>()
@@ -47,22 +45,29 @@ pub extern "C" fn matrix_multiply(mat: SEXP, vec: SEXP) -> SEXP {
         .expect_r("vec is not a numeric vector")
         .protect();
 
-    let mat_rows = mat.nrows();
-    let mat_cols = mat.ncols();
+    #[cfg(feature = "linalg")]
+    let out = mat.matvec(&vec).unwrap_r();
 
-    if mat_cols != vec.len() {
-        Err::<(), _>("mat_cols != vec.len()").unwrap_r();
-    }
+    #[cfg(not(feature = "linalg"))]
+    let out = {
+        let mat_rows = mat.nrows();
+        let mat_cols = mat.ncols();
+
+        if mat_cols != vec.len() {
+            Err::<(), _>("mat_cols != vec.len()").unwrap_r();
+        }
 
-    let mut out = RealVector::new(mat_rows).protect();
+        let mut out = RealVector::new(mat_rows).protect();
 
-    for i in 0..mat_rows {
-        let mut sum = 0.0;
-        for j in 0..mat_cols {
-            sum += mat[(i, j)] * vec[j];
+        for i in 0..mat_rows {
+            let mut sum = 0.0;
+            for j in 0..mat_cols {
+                sum += mat[[i, j]] * vec[j];
+            }
+            out[i] = sum;
         }
-        out[i] = sum;
-    }
+        out
+    };
 
     out.get_sexp()
 }
@@ -70,6 +75,7 @@ pub extern "C" fn matrix_multiply(mat: SEXP, vec: SEXP) -> SEXP {
 /// A .Call() function that takes an R callable and calls it with two arguments.
 #[export_name = "call_back"]
 pub extern "C" fn call_back(cb: SEXP, number_arg: SEXP) -> SEXP {
+    MainThread::register();
     let cb = cb
         .downcast_to::<Callable<_>>()
         .expect_r("cb is not a callable")
@@ -78,10 +84,7 @@ pub extern "C" fn call_back(cb: SEXP, number_arg: SEXP) -> SEXP {
     cb.build_pairlist()
         .push(IntegerVectorSEXP::scalar(1).protect())
         .push(CharacterVectorSEXP::scalar("Hello, world!").protect())
-        .push_tagged(
-            Symbol::new("number").protect(),
-            number_arg.coerce(SEXPTYPE::REALSXP).protect(),
-        )
+        .push_tagged("number", number_arg.coerce(SEXPTYPE::REALSXP).protect())
         .build_lang()
         .eval(Env::current())
         .unwrap_r()
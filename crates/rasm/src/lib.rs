@@ -12,7 +12,11 @@ use elf::{abi::STV_DEFAULT, endian::AnyEndian, section::SectionHeader, ElfBytes}
 use relocate::ComputeRelocation;
 use typed_sexp::{
     prelude::*,
-    sexp::{ptr::Ptr, vector::List},
+    sexp::{
+        ptr::{Ptr, PtrType},
+        vector::List,
+    },
+    thread::MainThread,
 };
 
 #[cfg(not(target_arch = "x86_64"))]
@@ -125,6 +129,10 @@ pub struct AsmFunction<I: ISA> {
     _isa: std::marker::PhantomData<I>,
 }
 
+impl PtrType for AsmFunction<X64ISA> {
+    const TYPE_ID: &'static str = concat!(env!("CARGO_PKG_NAME"), "@", env!("CARGO_PKG_VERSION"));
+}
+
 macro_rules! generate_asmcall {
     ($($name:ident( $( $arg_name:ident: $arg_ty:ident ),*))*) => {
         $(
@@ -338,6 +346,7 @@ mod ffi {
     #[export_name = "assemble"]
     /// R external function to assemble a string into a module.
     pub extern "C" fn assemble(input: SEXP) -> SEXP {
+        MainThread::register();
         let input = input
             .downcast_to::<CharacterVectorSEXP<_>>()
             .expect_r("input is not a string")
@@ -353,7 +362,7 @@ mod ffi {
 
         let ptr_inner = CharacterVectorSEXP::scalar("<asm_function>").protect();
 
-        let ptr = Ptr::<SEXP, AsmFunction<X64ISA>>::wrap_boxed(f, r_nil(), ptr_inner);
+        let ptr = Ptr::<SEXP, AsmFunction<X64ISA>>::wrap_typed(f, ptr_inner);
 
         ptr.get_sexp()
     }
@@ -361,6 +370,7 @@ mod ffi {
     #[export_name = "init_rustlog"]
     /// R external function to initialize the logger.
     pub extern "C" fn init_rustlog() -> SEXP {
+        MainThread::register();
         if std::env::var("RUST_LOG").is_err() {
             std::env::set_var("RUST_LOG", "warn");
         }
@@ -372,9 +382,10 @@ mod ffi {
     #[export_name = "asm_call"]
     /// R external function to call a function in an assembled module.
     pub extern "C" fn call(f: SEXP, name: SEXP, param: SEXP) -> SEXP {
+        MainThread::register();
         let f = f
             .downcast_to::<Ptr<SEXP, AsmFunction<X64ISA>>>()
-            .expect_r("f is not a vector")
+            .expect_r("f is not an assembled function")
             .protect();
 
         let f = f.get_ref();
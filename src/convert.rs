@@ -0,0 +1,320 @@
+//! Conversions between Rust values and R objects.
+//!
+//! These are used to pass arguments to and get results from [`Callable::call`](crate::sexp::function::Callable::call).
+
+use libR_sys::*;
+
+use crate::{
+    prelude::*,
+    protect::Protected,
+    sexp::{
+        env::Symbol,
+        vector::{IntegerVector, LogicalVector, RealVector},
+    },
+};
+
+/// Conversion of a Rust value into a new R object.
+pub trait IntoSexp {
+    /// Convert the value into an R object.
+    ///
+    /// The returned object is not protected.
+    fn into_sexp(self) -> SEXP;
+}
+
+/// Conversion of an R object into a Rust value.
+pub trait FromSexp: Sized {
+    /// Convert the R object, returning [`None`] if it has the wrong type or length.
+    fn from_sexp(sexp: SEXP) -> Option<Self>;
+}
+
+impl IntoSexp for SEXP {
+    fn into_sexp(self) -> SEXP {
+        self
+    }
+}
+
+impl<T: HasSEXP> IntoSexp for &T {
+    fn into_sexp(self) -> SEXP {
+        self.get_sexp()
+    }
+}
+
+impl<T: IntoSexp> IntoSexp for Option<T> {
+    fn into_sexp(self) -> SEXP {
+        match self {
+            Some(value) => value.into_sexp(),
+            None => r_nil(),
+        }
+    }
+}
+
+impl IntoSexp for f64 {
+    fn into_sexp(self) -> SEXP {
+        RealVector::scalar(self).get_sexp()
+    }
+}
+
+impl IntoSexp for i32 {
+    fn into_sexp(self) -> SEXP {
+        IntegerVector::scalar(self).get_sexp()
+    }
+}
+
+impl IntoSexp for bool {
+    fn into_sexp(self) -> SEXP {
+        LogicalVector::scalar_bool(self).get_sexp()
+    }
+}
+
+impl IntoSexp for &str {
+    fn into_sexp(self) -> SEXP {
+        CharacterVectorSEXP::scalar(self).get_sexp()
+    }
+}
+
+impl IntoSexp for String {
+    fn into_sexp(self) -> SEXP {
+        self.as_str().into_sexp()
+    }
+}
+
+impl IntoSexp for &[f64] {
+    fn into_sexp(self) -> SEXP {
+        let mut vector = RealVector::new(self.len());
+        vector.as_mut_slice().copy_from_slice(self);
+        vector.get_sexp()
+    }
+}
+
+impl IntoSexp for Vec<f64> {
+    fn into_sexp(self) -> SEXP {
+        self.as_slice().into_sexp()
+    }
+}
+
+impl IntoSexp for &[i32] {
+    fn into_sexp(self) -> SEXP {
+        let mut vector = IntegerVector::new(self.len());
+        vector.as_mut_slice().copy_from_slice(self);
+        vector.get_sexp()
+    }
+}
+
+impl IntoSexp for Vec<i32> {
+    fn into_sexp(self) -> SEXP {
+        self.as_slice().into_sexp()
+    }
+}
+
+impl IntoSexp for &[&str] {
+    fn into_sexp(self) -> SEXP {
+        let mut vector = CharacterVectorSEXP::new(self.len()).protect();
+        for (i, value) in self.iter().enumerate() {
+            vector.set_str(i, *value);
+        }
+        vector.get_sexp()
+    }
+}
+
+impl IntoSexp for &[String] {
+    fn into_sexp(self) -> SEXP {
+        let mut vector = CharacterVectorSEXP::new(self.len()).protect();
+        for (i, value) in self.iter().enumerate() {
+            vector.set_str(i, value);
+        }
+        vector.get_sexp()
+    }
+}
+
+impl IntoSexp for Vec<String> {
+    fn into_sexp(self) -> SEXP {
+        self.as_slice().into_sexp()
+    }
+}
+
+impl<T: JustSEXP> FromSexp for T {
+    fn from_sexp(sexp: SEXP) -> Option<Self> {
+        T::wrap_sexp(sexp)
+    }
+}
+
+impl FromSexp for AnySexp<SEXP> {
+    fn from_sexp(sexp: SEXP) -> Option<Self> {
+        Some(AnySexp::from(sexp))
+    }
+}
+
+impl FromSexp for () {
+    fn from_sexp(_sexp: SEXP) -> Option<Self> {
+        Some(())
+    }
+}
+
+impl<T: FromSexp> FromSexp for Option<T> {
+    fn from_sexp(sexp: SEXP) -> Option<Self> {
+        if sexp == r_nil() {
+            Some(None)
+        } else {
+            T::from_sexp(sexp).map(Some)
+        }
+    }
+}
+
+impl FromSexp for f64 {
+    fn from_sexp(sexp: SEXP) -> Option<Self> {
+        match RealVector::<SEXP>::wrap_sexp(sexp)?.as_slice() {
+            [value] => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromSexp for i32 {
+    fn from_sexp(sexp: SEXP) -> Option<Self> {
+        match IntegerVector::<SEXP>::wrap_sexp(sexp)?.as_slice() {
+            [value] => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl FromSexp for bool {
+    /// `NA` is not converted.
+    fn from_sexp(sexp: SEXP) -> Option<Self> {
+        match LogicalVector::<SEXP>::wrap_sexp(sexp)?.as_slice() {
+            [value] if *value != unsafe { R_NaInt } => Some(*value != 0),
+            _ => None,
+        }
+    }
+}
+
+impl FromSexp for String {
+    /// `NA` is not converted.
+    fn from_sexp(sexp: SEXP) -> Option<Self> {
+        let vector = CharacterVectorSEXP::<SEXP>::wrap_sexp(sexp)?;
+        if vector.len() != 1 {
+            return None;
+        }
+        vector.get_elt(0).as_str().map(ToOwned::to_owned)
+    }
+}
+
+impl FromSexp for Vec<f64> {
+    fn from_sexp(sexp: SEXP) -> Option<Self> {
+        Some(RealVector::<SEXP>::wrap_sexp(sexp)?.as_slice().to_vec())
+    }
+}
+
+impl FromSexp for Vec<i32> {
+    fn from_sexp(sexp: SEXP) -> Option<Self> {
+        Some(IntegerVector::<SEXP>::wrap_sexp(sexp)?.as_slice().to_vec())
+    }
+}
+
+impl FromSexp for Vec<String> {
+    /// `NA` elements are not converted.
+    fn from_sexp(sexp: SEXP) -> Option<Self> {
+        let vector = CharacterVectorSEXP::<SEXP>::wrap_sexp(sexp)?;
+        (0..vector.len())
+            .map(|i| vector.get_elt(i).as_str().map(ToOwned::to_owned))
+            .collect()
+    }
+}
+
+/// A named argument, like `name = value` in a call.
+///
+/// Created with [`named`].
+pub struct Named<V> {
+    name: Symbol<SEXP>,
+    value: V,
+}
+
+/// Pass a value as a named argument to [`Callable::call`](crate::sexp::function::Callable::call).
+pub fn named<V: IntoSexp>(name: &str, value: V) -> Named<V> {
+    Named {
+        name: Symbol::new(name),
+        value,
+    }
+}
+
+/// The arguments of a call, protected until the call is finished.
+pub struct CallArgs {
+    args: Vec<(Option<Symbol<SEXP>>, SEXP)>,
+    guards: Vec<Protected<SEXP>>,
+}
+
+impl CallArgs {
+    pub(crate) fn new() -> Self {
+        CallArgs {
+            args: Vec::new(),
+            guards: Vec::new(),
+        }
+    }
+
+    /// Convert and protect an argument, tagged with a name if it is given.
+    #[track_caller]
+    pub fn push(&mut self, name: Option<Symbol<SEXP>>, value: impl IntoSexp) {
+        let sexp = value.into_sexp();
+        self.guards.push(Protected::new(sexp));
+        self.args.push((name, sexp));
+    }
+
+    pub(crate) fn as_slice(&self) -> &[(Option<Symbol<SEXP>>, SEXP)] {
+        &self.args
+    }
+}
+
+impl Drop for CallArgs {
+    fn drop(&mut self) {
+        // Unprotect in reverse order, a `Vec` drops its elements front to back.
+        while let Some(guard) = self.guards.pop() {
+            drop(guard);
+        }
+    }
+}
+
+/// A single argument of a call, either positional or [`Named`].
+pub trait IntoArg {
+    /// Add the argument to the call.
+    fn push_arg(self, args: &mut CallArgs);
+}
+
+impl<V: IntoSexp> IntoArg for V {
+    fn push_arg(self, args: &mut CallArgs) {
+        args.push(None, self);
+    }
+}
+
+impl<V: IntoSexp> IntoArg for Named<V> {
+    fn push_arg(self, args: &mut CallArgs) {
+        args.push(Some(self.name), self.value);
+    }
+}
+
+/// The arguments of a call, a tuple of [`IntoArg`] values.
+pub trait IntoArgs {
+    /// Add all arguments to the call, in order.
+    fn push_args(self, args: &mut CallArgs);
+}
+
+macro_rules! impl_into_args {
+    ($($name:ident),*) => {
+        impl<$($name: IntoArg),*> IntoArgs for ($($name,)*) {
+            #[allow(non_snake_case, unused_variables)]
+            fn push_args(self, args: &mut CallArgs) {
+                let ($($name,)*) = self;
+                $($name.push_arg(args);)*
+            }
+        }
+    };
+}
+
+impl_into_args!();
+impl_into_args!(A);
+impl_into_args!(A, B);
+impl_into_args!(A, B, C);
+impl_into_args!(A, B, C, D);
+impl_into_args!(A, B, C, D, E);
+impl_into_args!(A, B, C, D, E, F);
+impl_into_args!(A, B, C, D, E, F, G);
+impl_into_args!(A, B, C, D, E, F, G, H);
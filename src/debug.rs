@@ -1,28 +1,157 @@
+//! Debugging aids: the checked protect stack of the `checked_protect_stack` feature and the handle tracking of `leak_tracking`.
+//!
+//! The checked protect stack shadows the values protected through [`Protected`](crate::protect::Protected),
+//! it does not see raw `Rf_protect` calls and is not compared with R's own protect stack, which is internal to R.
+//! When an R error longjmps past Rust frames holding guards, those guards are never dropped. The crate's error boundaries
+//! ([`Lang::eval`](crate::sexp::lang::Lang::eval), [`Callable::call`](crate::sexp::function::Callable::call),
+//! promise forcing, namespace loading and [`check_interrupt`](crate::interrupt::check_interrupt)) discard their entries,
+//! but an error caught elsewhere, e.g. by R code around a `.Call` that used `unwrap_r`, leaves stale entries
+//! that are later reported as values unprotected out of order.
 #![allow(dead_code)]
+use std::{cell::RefCell, panic::Location};
+
 use libR_sys::SEXP;
 
-pub(crate) static mut PROTECT_STACK_CHECK: CheckedProtectStack = CheckedProtectStack::new();
+thread_local! {
+    static PROTECT_STACK_CHECK: RefCell<CheckedProtectStack> = const { RefCell::new(CheckedProtectStack::new()) };
+}
+
+/// Record that `sexp` was just protected, by a call at `location`.
+#[track_caller]
+pub(crate) fn protect_stack_push(sexp: SEXP) {
+    let location = Location::caller();
+    PROTECT_STACK_CHECK.with(|stack| stack.borrow_mut().push(sexp, location));
+}
+
+/// Check that `sexp` is on top of the protect stack and was just unprotected.
+pub(crate) fn protect_stack_checked_pop(sexp: SEXP) {
+    PROTECT_STACK_CHECK.with(|stack| stack.borrow_mut().checked_pop(sexp));
+}
+
+/// A value protected through [`Protected`](crate::protect::Protected).
+struct ProtectEntry {
+    sexp: SEXP,
+    /// Where the value was protected.
+    location: &'static Location<'static>,
+}
 
+/// A shadow of the values protected through [`Protected`](crate::protect::Protected), with the source locations they were protected at.
+///
+/// Only values protected by this crate are counted, raw `Rf_protect` calls are not seen.
 pub(crate) struct CheckedProtectStack {
-    seen_sexps: Vec<SEXP>,
+    entries: Vec<ProtectEntry>,
 }
 
 impl CheckedProtectStack {
     pub(crate) const fn new() -> Self {
         CheckedProtectStack {
-            seen_sexps: Vec::new(),
+            entries: Vec::new(),
         }
     }
 
-    pub(crate) fn push(&mut self, sexp: SEXP) {
-        self.seen_sexps.push(sexp);
+    pub(crate) fn push(&mut self, sexp: SEXP, location: &'static Location<'static>) {
+        self.entries.push(ProtectEntry { sexp, location });
     }
 
     pub(crate) fn checked_pop(&mut self, sexp: SEXP) {
-        if let Some(last) = self.seen_sexps.pop() {
-            assert_eq!(last, sexp, "Incorrect SEXP popped from protect stack");
-        } else {
-            panic!("Unbalanced protect/unprotect calls");
+        let Some(entry) = self.entries.pop() else {
+            report(format!(
+                "Unbalanced protect/unprotect: {:?} is not on the checked protect stack",
+                sexp
+            ));
+            return;
+        };
+        if entry.sexp != sexp {
+            let dropped = self
+                .entries
+                .iter()
+                .rev()
+                .find(|other| other.sexp == sexp)
+                .map(|other| other.location.to_string())
+                .unwrap_or_else(|| "an unknown location".to_string());
+            report(format!(
+                "Protected values unprotected out of order: the value protected at {} was unprotected \
+                 while the value protected at {} is still on top of the protect stack",
+                dropped, entry.location
+            ));
         }
     }
 }
+
+/// Panic with `message`, or print it to stderr if the thread is already panicking, since a second panic would abort.
+///
+/// Imbalances found while unwinding, e.g. when guards are dropped after a failed assertion in a test,
+/// are therefore only printed and do not fail the test by themselves.
+fn report(message: String) {
+    if std::thread::panicking() {
+        eprintln!("{}", message);
+    } else {
+        panic!("{}", message);
+    }
+}
+
+/// The number of values currently protected through this crate on this thread.
+pub(crate) fn protect_stack_len() -> usize {
+    PROTECT_STACK_CHECK.with(|stack| stack.borrow().entries.len())
+}
+
+/// Discard the entries above `len`, taken with [`protect_stack_len`] before R reported an error.
+///
+/// R restored its own protect stack when it unwound, but the guards in Rust frames it jumped over were never dropped.
+pub(crate) fn protect_stack_truncate(len: usize) {
+    PROTECT_STACK_CHECK.with(|stack| stack.borrow_mut().entries.truncate(len));
+}
+
+#[cfg(feature = "leak_tracking")]
+pub(crate) use leak::*;
+
+#[cfg(feature = "leak_tracking")]
+mod leak {
+    use std::{backtrace::Backtrace, cell::RefCell, rc::Rc};
+
+    use libR_sys::SEXP;
+
+    use crate::protect::{HandleKind, LiveHandle};
+
+    thread_local! {
+        static LIVE_HANDLES: RefCell<(u64, Vec<LiveHandle>)> = const { RefCell::new((0, Vec::new())) };
+    }
+
+    /// Record a new handle keeping `sexp` alive, returning its id.
+    pub(crate) fn track_handle(kind: HandleKind, sexp: SEXP, type_name: &'static str) -> u64 {
+        let backtrace = Rc::new(Backtrace::force_capture());
+        LIVE_HANDLES.with(|handles| {
+            let (next_id, handles) = &mut *handles.borrow_mut();
+            let id = *next_id;
+            *next_id += 1;
+            handles.push(LiveHandle {
+                id,
+                kind,
+                sexp,
+                type_name,
+                backtrace,
+            });
+            id
+        })
+    }
+
+    /// Forget the handle with the given id, after it released its object.
+    pub(crate) fn untrack_handle(id: u64) {
+        LIVE_HANDLES.with(|handles| handles.borrow_mut().1.retain(|handle| handle.id != id));
+    }
+
+    /// Forget the external pointer `sexp`, after its finalizer ran.
+    pub(crate) fn untrack_external_ptr(sexp: SEXP) {
+        LIVE_HANDLES.with(|handles| {
+            handles
+                .borrow_mut()
+                .1
+                .retain(|handle| !(handle.kind == HandleKind::ExternalPtr && handle.sexp == sexp))
+        });
+    }
+
+    /// All handles that were not released yet, oldest first.
+    pub(crate) fn tracked_handles() -> Vec<LiveHandle> {
+        LIVE_HANDLES.with(|handles| handles.borrow().1.clone())
+    }
+}
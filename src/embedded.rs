@@ -3,12 +3,19 @@ use std::process::Command;
 
 use libR_sys::*;
 
+use crate::thread::MainThread;
+
 /// An embedded R instance.
-pub struct EmbeddedR;
+///
+/// R runs on the thread that initialized it, so this is neither [`Send`] nor [`Sync`].
+pub struct EmbeddedR {
+    _main_thread: MainThread,
+}
 
 impl EmbeddedR {
-    /// Initialize embedded R.
+    /// Initialize embedded R, recording the current thread as the R main thread.
     pub unsafe fn init() -> Self {
+        let main_thread = MainThread::register();
         if std::env::var("R_HOME").is_err() {
             let out = Command::new("R")
                 .arg("-s")
@@ -29,7 +36,9 @@ impl EmbeddedR {
         R_CStackLimit = usize::MAX;
         setup_Rmainloop();
 
-        EmbeddedR
+        EmbeddedR {
+            _main_thread: main_thread,
+        }
     }
 }
 
@@ -38,5 +47,10 @@ impl Drop for EmbeddedR {
         unsafe {
             libR_sys::Rf_endEmbeddedR(0);
         }
+        // Finalizers of external pointers ran at exit, so what is left was leaked.
+        #[cfg(feature = "leak_tracking")]
+        if !crate::protect::live_handles().is_empty() {
+            eprintln!("{}", crate::protect::leak_report());
+        }
     }
 }
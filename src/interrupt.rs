@@ -0,0 +1,98 @@
+//! Checking for user interrupts without jumping over Rust frames.
+//!
+//! `R_CheckUserInterrupt` longjmps to the top level when the user pressed Ctrl-C, skipping all Rust destructors.
+//! [`check_interrupt`] returns an error instead, so a cancelled computation can unwind normally and drop its [`Protected`](crate::protect::Protected) guards.
+use std::{ffi::c_void, fmt::Display};
+
+use libR_sys::*;
+
+/// The user interrupted R, e.g. with Ctrl-C.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Interrupted;
+
+impl Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "interrupted by the user")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Check if the user interrupted R, like `R_CheckUserInterrupt` but returning an error instead of jumping.
+///
+/// This also runs R's event loop, so the console stays responsive.
+/// The interrupt is consumed, so it is up to the caller to stop and report it.
+pub fn check_interrupt() -> Result<(), Interrupted> {
+    unsafe extern "C" fn check(_data: *mut c_void) {
+        R_CheckUserInterrupt();
+    }
+
+    crate::thread::debug_check_main_thread();
+    let checked = crate::debug::protect_stack_len();
+    let completed: bool = unsafe { R_ToplevelExec(Some(check), std::ptr::null_mut()) }.into();
+    if completed {
+        Ok(())
+    } else {
+        crate::debug::protect_stack_truncate(checked);
+        Err(Interrupted)
+    }
+}
+
+/// An iterator adapter that checks for user interrupts, created by [`Interruptible::interruptible`].
+pub struct InterruptibleIter<I> {
+    iter: I,
+    every: usize,
+    count: usize,
+    interrupted: bool,
+}
+
+impl<I: Iterator> Iterator for InterruptibleIter<I> {
+    type Item = Result<I::Item, Interrupted>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.interrupted {
+            return None;
+        }
+        self.count += 1;
+        if self.count >= self.every {
+            self.count = 0;
+            if let Err(interrupted) = check_interrupt() {
+                self.interrupted = true;
+                return Some(Err(interrupted));
+            }
+        }
+        self.iter.next().map(Ok)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.interrupted {
+            return (0, Some(0));
+        }
+        // An interrupt can end the iteration early, or add an error item.
+        let (_, upper) = self.iter.size_hint();
+        (0, upper.and_then(|upper| upper.checked_add(1)))
+    }
+}
+
+/// Extension trait for checking for user interrupts while iterating.
+pub trait Interruptible: Iterator + Sized {
+    /// Check for user interrupts every `every` items.
+    ///
+    /// Items are wrapped in [`Ok`], an interrupt yields a single [`Err`] and ends the iteration,
+    /// so the result can be collected into a `Result<Vec<_>, Interrupted>` or used with `?`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `every` is zero.
+    fn interruptible(self, every: usize) -> InterruptibleIter<Self> {
+        assert!(every > 0, "the interrupt check interval must be positive");
+        InterruptibleIter {
+            iter: self,
+            every,
+            count: 0,
+            interrupted: false,
+        }
+    }
+}
+
+impl<I: Iterator> Interruptible for I {}
@@ -20,13 +20,20 @@
 //! ## Features
 //!
 //! - Typed SEXP objects that are ABI-compatible with R's SEXP.
-//! - Type-safe (mutable) indexing of vectors and matrices.
+//! - Type-safe (mutable) indexing of vectors, matrices and N-dimensional arrays.
 //! - Typed protection of SEXP objects with a debug mode that sanity-checks the protection order.
+//! - Main-thread tokens and debug-mode checks against using R objects from other threads.
+//! - User interrupt checks that return an error instead of jumping over Rust frames.
+//! - Leak tracking of preserved objects and external pointers with creation backtraces (`leak_tracking` feature).
+//! - GC-torture test harness that catches missing protection (`embedded` feature).
 //! - Stack RAII-based auto un-protection of SEXP objects.
+//! - Lifetime-branded protection scopes whose allocations cannot outlive the scope.
 //! - Downcasting of SEXP objects in a method chain with a single call (even if the object is wrapped in another abstraction).
 //! - Namespace and function objects that are environment-aware.
 //! - Call R functions with type-safe arguments and return values.
-//! - (TODO): Dynamically create R functions.
+//! - Matrix multiply, solve, Cholesky, QR and SVD through R's own BLAS and LAPACK.
+//! - Optional zero-copy [`ndarray`](https://docs.rs/ndarray) and [`nalgebra`](https://docs.rs/nalgebra) views over vectors and matrices.
+//! - Dynamically create R functions from formals, a body and an environment.
 #![warn(missing_docs)]
 
 use libR_sys::{SEXPTYPE::*, *};
@@ -37,19 +44,32 @@ pub use libR_sys;
 pub use libR_sys::SEXP;
 use sexp::{
     env::{Env, Symbol},
-    function::{Builtin, Closure, Function},
+    function::{Builtin, CallError, Closure, Function},
     lang::{Lang, PairlistBuilder},
     matrix::Matrix,
+    promise::Promise,
+    s4::S4Object,
     vector::{CharacterVector, IntegerVector, List, LogicalVector, RealVector},
 };
 
+pub mod convert;
 pub(crate) mod debug;
 #[cfg(feature = "embedded")]
 pub mod embedded;
+pub mod interrupt;
+#[cfg(feature = "linalg")]
+pub mod linalg;
 pub mod message;
+#[cfg(feature = "nalgebra")]
+pub mod nalgebra;
+#[cfg(feature = "ndarray")]
+pub mod ndarray;
 pub mod prelude;
 pub mod protect;
 pub mod sexp;
+pub mod thread;
+#[cfg(feature = "embedded")]
+pub mod torture;
 
 /// Any supported SEXP type.
 #[allow(missing_docs)]
@@ -71,6 +91,8 @@ pub enum AnySexp<T: JustSEXP> {
     Builtin(Builtin<T>),
     Closure(Closure<T>),
     Environment(Env<T>),
+    Promise(Promise<T>),
+    S4(S4Object<T>),
     Other(T),
 }
 
@@ -100,8 +122,12 @@ impl<T: JustSEXP> From<T> for AnySexp<T> {
             SYMSXP => AnySexp::Symbol(unsafe { Symbol::wrap_sexp_unchecked(sexp) }),
             LANGSXP => AnySexp::Lang(unsafe { Lang::wrap_sexp_unchecked(sexp) }),
             ENVSXP => AnySexp::Environment(unsafe { Env::wrap_sexp_unchecked(sexp) }),
+            PROMSXP => AnySexp::Promise(unsafe { Promise::wrap_sexp_unchecked(sexp) }),
             FUNSXP => AnySexp::Function(unsafe { Function::wrap_sexp_unchecked(sexp) }),
             VECSXP => AnySexp::List(unsafe { List::wrap_sexp_unchecked(sexp) }),
+            // Also `OBJSXP`, the same type code under its R 4.4 name. Vectors and environments with the S4 bit
+            // keep their own variant, they can still be downcast to `S4Object` explicitly.
+            S4SXP => AnySexp::S4(unsafe { S4Object::wrap_sexp_unchecked(sexp) }),
             _ => {
                 if let Some(builtin) = Builtin::<T>::wrap_sexp(sexp) {
                     AnySexp::Builtin(builtin)
@@ -134,6 +160,8 @@ impl<T: JustSEXP> AnySexp<T> {
             AnySexp::Builtin(value) => value.upcast(),
             AnySexp::Closure(value) => value.upcast(),
             AnySexp::Environment(value) => value.upcast(),
+            AnySexp::Promise(value) => value.upcast(),
+            AnySexp::S4(value) => value.upcast(),
             AnySexp::List(value) => value.upcast(),
             AnySexp::Other(value) => value,
         }
@@ -156,6 +184,8 @@ impl<T: JustSEXP> AnySexp<T> {
             AnySexp::Builtin(value) => value.inner_ref(),
             AnySexp::Closure(value) => value.inner_ref(),
             AnySexp::Environment(value) => value.inner_ref(),
+            AnySexp::Promise(value) => value.inner_ref(),
+            AnySexp::S4(value) => value.inner_ref(),
             AnySexp::List(value) => value.inner_ref(),
             AnySexp::Other(value) => value,
         }
@@ -179,6 +209,8 @@ impl<T: JustSEXP> HasSEXP for AnySexp<T> {
             AnySexp::Builtin(value) => value.get_sexp(),
             AnySexp::Closure(value) => value.get_sexp(),
             AnySexp::Environment(value) => value.get_sexp(),
+            AnySexp::Promise(value) => value.get_sexp(),
+            AnySexp::S4(value) => value.get_sexp(),
             AnySexp::List(value) => value.get_sexp(),
             AnySexp::Other(value) => value.get_sexp(),
         }
@@ -196,6 +228,26 @@ pub trait TypedSEXP: HasSEXP {
     const SEXP_TYPE: SEXPTYPE;
 }
 
+/// The default `control` options of R's `deparse`, for [`HasSEXP::deparse`].
+pub const DEPARSE_DEFAULT_OPTIONS: &[&str] =
+    &["keepNA", "keepInteger", "niceNames", "showAttributes"];
+
+/// The default line width of R's `deparse`, for [`HasSEXP::deparse`].
+pub const DEPARSE_DEFAULT_WIDTH: usize = 60;
+
+/// Deparse `value` with the default options for a [`Debug`](std::fmt::Debug) impl.
+///
+/// Returns [`None`] off the R main thread, where R can't be called, or if deparsing fails.
+pub(crate) fn deparse_for_debug(value: &impl HasSEXP) -> Option<String> {
+    if !thread::is_main_thread() {
+        return None;
+    }
+    value
+        .deparse(DEPARSE_DEFAULT_WIDTH, DEPARSE_DEFAULT_OPTIONS)
+        .ok()
+        .map(|code| code.join("\n"))
+}
+
 /// A trait for objects that have an underlying [`SEXP`].
 pub trait HasSEXP {
     /// Get the underlying SEXP.
@@ -203,16 +255,19 @@ pub trait HasSEXP {
 
     /// Check if the SEXP is null.
     fn is_sexp_null(&self) -> bool {
+        thread::debug_check_main_thread();
         unsafe { self.get_sexp() == libR_sys::R_NilValue }
     }
 
     /// Get the type of the SEXP.
     fn sexp_type(&self) -> SEXPTYPE {
+        thread::debug_check_main_thread();
         unsafe { TYPEOF(self.get_sexp()) }
     }
 
     /// Print the value to the R console.
     fn r_print(&self) {
+        thread::debug_check_main_thread();
         unsafe {
             Rf_PrintValue(self.get_sexp());
         }
@@ -220,15 +275,42 @@ pub trait HasSEXP {
 
     /// Get the attribute of the SEXP.
     fn attrib(&self, tag: SEXP) -> SEXP {
+        thread::debug_check_main_thread();
         unsafe { Rf_getAttrib(self.get_sexp(), tag) }
     }
 
     /// Coerce the underlying SEXP to the given type.
     fn coerce(&self, sexp_type: SEXPTYPE) -> SEXP {
+        thread::debug_check_main_thread();
         unsafe { Rf_coerceVector(self.get_sexp(), sexp_type) }
     }
 
+    /// Deparse the object into lines of R code, like `deparse(x, width.cutoff = width, control = options)`.
+    ///
+    /// `options` are the `control` options of `deparse`, R's defaults are [`DEPARSE_DEFAULT_OPTIONS`].
+    fn deparse(&self, width: usize, options: &[&str]) -> Result<Vec<String>, CallError> {
+        // The caller may hold `self` unprotected, and building the call allocates.
+        let this = self.get_sexp().protect();
+        // Quoted, since call arguments are evaluated.
+        let quoted = sexp::expr::call(
+            sexp::expr::sym("quote"),
+            [sexp::expr::Expr::Value(this.get_sexp())],
+        )
+        .to_sexp()
+        .protect();
+        sexp::namespace::call_in_namespace(
+            "base",
+            "deparse",
+            (
+                &quoted,
+                convert::named("width.cutoff", width.clamp(20, 500) as i32),
+                convert::named("control", options),
+            ),
+        )
+    }
+
     /// Protect the object in the protection stack.
+    #[track_caller]
     fn protect(self) -> Protected<Self>
     where
         Self: Sized,
@@ -296,6 +378,7 @@ pub trait IndexableSEXP: HasSEXP {
 ///
 /// - Both types should have the same size and alignment as [`SEXP`], and should just be a wrapper around it.
 /// - [`Drop`] implementation for this type may or may not be called.
+/// - The type must not be [`Send`] or [`Sync`], see [`thread`]. Holding a [`SEXP`] ensures this.
 pub unsafe trait JustSEXP: HasSEXP + Sized {
     /// The inner type that this type wraps, of course it has to be also [`JustSEXP`].
     type Inner: JustSEXP;
@@ -346,7 +429,7 @@ pub unsafe trait ProtectedSEXP: HasSEXP {
     /// The inner type that this type wraps.
     type Inner: HasSEXP;
 
-    /// Forget the object and return it.
+    /// Forget the object and return it, leaving it protected.
     fn forget(self) -> Self::Inner;
 
     /// Unprotect the object and return it.
@@ -0,0 +1,482 @@
+//! Dense linear algebra on real matrices through the BLAS and LAPACK that R is linked against.
+//!
+//! Results match what R's own `%*%`, `solve`, `chol` and `La.svd` compute, and users' optimized BLAS is picked up automatically.
+//!
+//! The build script links the libraries reported by `R CMD config BLAS_LIBS` and `LAPACK_LIBS`,
+//! which can be overridden with the `R_BLAS_LIBS` and `R_LAPACK_LIBS` environment variables.
+//! Without R at build time nothing is linked, and the symbols are resolved from the R process the extension is loaded into.
+use std::{
+    ffi::{c_char, c_int},
+    fmt::Display,
+};
+
+use crate::{
+    protect::{BoxProtected, Protected},
+    sexp::{matrix::Matrix, vector::RealVector},
+    HasSEXP, IndexableSEXP, JustSEXP, SEXP,
+};
+
+extern "C" {
+    fn dgemm_(
+        transa: *const c_char,
+        transb: *const c_char,
+        m: *const c_int,
+        n: *const c_int,
+        k: *const c_int,
+        alpha: *const f64,
+        a: *const f64,
+        lda: *const c_int,
+        b: *const f64,
+        ldb: *const c_int,
+        beta: *const f64,
+        c: *mut f64,
+        ldc: *const c_int,
+        transa_len: usize,
+        transb_len: usize,
+    );
+    fn dgemv_(
+        trans: *const c_char,
+        m: *const c_int,
+        n: *const c_int,
+        alpha: *const f64,
+        a: *const f64,
+        lda: *const c_int,
+        x: *const f64,
+        incx: *const c_int,
+        beta: *const f64,
+        y: *mut f64,
+        incy: *const c_int,
+        trans_len: usize,
+    );
+}
+
+extern "C" {
+    fn dgesv_(
+        n: *const c_int,
+        nrhs: *const c_int,
+        a: *mut f64,
+        lda: *const c_int,
+        ipiv: *mut c_int,
+        b: *mut f64,
+        ldb: *const c_int,
+        info: *mut c_int,
+    );
+    fn dpotrf_(
+        uplo: *const c_char,
+        n: *const c_int,
+        a: *mut f64,
+        lda: *const c_int,
+        info: *mut c_int,
+        uplo_len: usize,
+    );
+    fn dgeqrf_(
+        m: *const c_int,
+        n: *const c_int,
+        a: *mut f64,
+        lda: *const c_int,
+        tau: *mut f64,
+        work: *mut f64,
+        lwork: *const c_int,
+        info: *mut c_int,
+    );
+    fn dorgqr_(
+        m: *const c_int,
+        n: *const c_int,
+        k: *const c_int,
+        a: *mut f64,
+        lda: *const c_int,
+        tau: *const f64,
+        work: *mut f64,
+        lwork: *const c_int,
+        info: *mut c_int,
+    );
+    fn dgesdd_(
+        jobz: *const c_char,
+        m: *const c_int,
+        n: *const c_int,
+        a: *mut f64,
+        lda: *const c_int,
+        s: *mut f64,
+        u: *mut f64,
+        ldu: *const c_int,
+        vt: *mut f64,
+        ldvt: *const c_int,
+        work: *mut f64,
+        lwork: *const c_int,
+        iwork: *mut c_int,
+        info: *mut c_int,
+        jobz_len: usize,
+    );
+}
+
+/// A Fortran single-character argument.
+fn fchar(c: &'static [u8; 1]) -> *const c_char {
+    c.as_ptr().cast()
+}
+
+/// A dimension as a Fortran integer.
+fn dim(n: usize) -> Result<c_int, LinalgError> {
+    c_int::try_from(n).map_err(|_| LinalgError::DimensionTooLarge(n))
+}
+
+/// A leading dimension, which LAPACK requires to be at least 1 even for empty matrices.
+fn ld(n: usize) -> Result<c_int, LinalgError> {
+    dim(n.max(1))
+}
+
+/// Errors from linear algebra routines.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinalgError {
+    /// The operands have incompatible dimensions.
+    DimensionMismatch {
+        /// The dimensions of the left operand.
+        lhs: (usize, usize),
+        /// The dimensions of the right operand.
+        rhs: (usize, usize),
+    },
+    /// The operation requires a square matrix.
+    NotSquare((usize, usize)),
+    /// A dimension or workspace size does not fit in a BLAS/LAPACK integer.
+    DimensionTooLarge(usize),
+    /// LAPACK returned a non-zero `info` code.
+    ///
+    /// A negative code means an illegal argument, a positive code is routine-specific, e.g. a singular matrix for `dgesv`.
+    Lapack {
+        /// The name of the LAPACK routine.
+        routine: &'static str,
+        /// The `info` code returned.
+        info: i32,
+    },
+}
+
+impl Display for LinalgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LinalgError::DimensionMismatch { lhs, rhs } => write!(
+                f,
+                "non-conformable arguments: {}x{} and {}x{}",
+                lhs.0, lhs.1, rhs.0, rhs.1
+            ),
+            LinalgError::NotSquare((nrow, ncol)) => {
+                write!(f, "matrix is not square: {}x{}", nrow, ncol)
+            }
+            LinalgError::DimensionTooLarge(n) => {
+                write!(f, "dimension {} is too large for BLAS/LAPACK", n)
+            }
+            LinalgError::Lapack { routine, info } => {
+                write!(f, "LAPACK routine '{}' returned info = {}", routine, info)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LinalgError {}
+
+fn check_info(routine: &'static str, info: c_int) -> Result<(), LinalgError> {
+    if info == 0 {
+        Ok(())
+    } else {
+        Err(LinalgError::Lapack { routine, info })
+    }
+}
+
+/// The result of [`Matrix::qr`], `A = QR`.
+pub struct Qr {
+    /// The `m x k` matrix with orthonormal columns.
+    pub q: BoxProtected<Matrix<RealVector<SEXP>>>,
+    /// The `k x n` upper triangular matrix.
+    pub r: BoxProtected<Matrix<RealVector<SEXP>>>,
+}
+
+/// The result of [`Matrix::svd`], `A = U diag(d) Vt`.
+pub struct Svd {
+    /// The singular values in decreasing order.
+    pub d: BoxProtected<RealVector<SEXP>>,
+    /// The `m x k` left singular vectors.
+    pub u: BoxProtected<Matrix<RealVector<SEXP>>>,
+    /// The `k x n` transposed right singular vectors.
+    pub vt: BoxProtected<Matrix<RealVector<SEXP>>>,
+}
+
+impl<T: JustSEXP> Matrix<RealVector<T>> {
+    fn dims(&self) -> (usize, usize) {
+        (self.nrows(), self.ncols())
+    }
+
+    /// Matrix product `self %*% rhs` through BLAS `dgemm`.
+    pub fn matmul<U: JustSEXP>(
+        &self,
+        rhs: &Matrix<RealVector<U>>,
+    ) -> Result<Protected<Matrix<RealVector<SEXP>>>, LinalgError> {
+        let (m, k) = self.dims();
+        let (k2, n) = rhs.dims();
+        if k != k2 {
+            return Err(LinalgError::DimensionMismatch {
+                lhs: (m, k),
+                rhs: (k2, n),
+            });
+        }
+
+        let (mi, ni, ki) = (dim(m)?, dim(n)?, dim(k)?);
+        let mut out = Matrix::<RealVector<SEXP>>::new(m, n).protect();
+        unsafe {
+            dgemm_(
+                fchar(b"N"),
+                fchar(b"N"),
+                &mi,
+                &ni,
+                &ki,
+                &1.0,
+                self.as_slice().as_ptr(),
+                &ld(m)?,
+                rhs.as_slice().as_ptr(),
+                &ld(k)?,
+                &0.0,
+                out.inner_mut().as_mut_slice().as_mut_ptr(),
+                &ld(m)?,
+                1,
+                1,
+            );
+        }
+        Ok(out)
+    }
+
+    /// Matrix-vector product `self %*% rhs` through BLAS `dgemv`.
+    pub fn matvec<U: HasSEXP>(
+        &self,
+        rhs: &RealVector<U>,
+    ) -> Result<Protected<RealVector<SEXP>>, LinalgError> {
+        let (m, n) = self.dims();
+        if n != rhs.len() {
+            return Err(LinalgError::DimensionMismatch {
+                lhs: (m, n),
+                rhs: (rhs.len(), 1),
+            });
+        }
+
+        let (mi, ni) = (dim(m)?, dim(n)?);
+        let mut out = RealVector::new(m).protect();
+        unsafe {
+            dgemv_(
+                fchar(b"N"),
+                &mi,
+                &ni,
+                &1.0,
+                self.as_slice().as_ptr(),
+                &ld(m)?,
+                rhs.as_slice().as_ptr(),
+                &1,
+                &0.0,
+                out.as_mut_slice().as_mut_ptr(),
+                &1,
+                1,
+            );
+        }
+        Ok(out)
+    }
+
+    /// Solve `self %*% x = rhs` for `x` through LAPACK `dgesv`, like R's `solve(a, b)`.
+    pub fn solve<U: JustSEXP>(
+        &self,
+        rhs: &Matrix<RealVector<U>>,
+    ) -> Result<Protected<Matrix<RealVector<SEXP>>>, LinalgError> {
+        let (n, n2) = self.dims();
+        if n != n2 {
+            return Err(LinalgError::NotSquare((n, n2)));
+        }
+        let (b_rows, nrhs) = rhs.dims();
+        if b_rows != n {
+            return Err(LinalgError::DimensionMismatch {
+                lhs: (n, n),
+                rhs: (b_rows, nrhs),
+            });
+        }
+
+        let (ni, nrhsi) = (dim(n)?, dim(nrhs)?);
+        let mut out = Matrix::<RealVector<SEXP>>::new(n, nrhs).protect();
+        out.inner_mut()
+            .as_mut_slice()
+            .copy_from_slice(rhs.as_slice());
+        let mut a = self.as_slice().to_vec();
+        let mut ipiv = vec![0; n];
+        let mut info = 0;
+        unsafe {
+            dgesv_(
+                &ni,
+                &nrhsi,
+                a.as_mut_ptr(),
+                &ld(n)?,
+                ipiv.as_mut_ptr(),
+                out.inner_mut().as_mut_slice().as_mut_ptr(),
+                &ld(n)?,
+                &mut info,
+            );
+        }
+        check_info("dgesv", info)?;
+        Ok(out)
+    }
+
+    /// The upper triangular Cholesky factor `R` with `t(R) %*% R = self`, through LAPACK `dpotrf`, like R's `chol`.
+    pub fn cholesky(&self) -> Result<Protected<Matrix<RealVector<SEXP>>>, LinalgError> {
+        let (n, n2) = self.dims();
+        if n != n2 {
+            return Err(LinalgError::NotSquare((n, n2)));
+        }
+
+        let ni = dim(n)?;
+        let mut out = Matrix::<RealVector<SEXP>>::new(n, n).protect();
+        let r = out.inner_mut().as_mut_slice();
+        r.copy_from_slice(self.as_slice());
+        let mut info = 0;
+        unsafe {
+            dpotrf_(fchar(b"U"), &ni, r.as_mut_ptr(), &ld(n)?, &mut info, 1);
+        }
+        check_info("dpotrf", info)?;
+        for j in 0..n {
+            r[j * n + j + 1..(j + 1) * n].fill(0.0);
+        }
+        Ok(out)
+    }
+
+    /// The thin QR decomposition through LAPACK `dgeqrf` and `dorgqr`.
+    ///
+    /// Unlike R's default `qr`, no column pivoting is done.
+    pub fn qr(&self) -> Result<Qr, LinalgError> {
+        let (m, n) = self.dims();
+        let k = m.min(n);
+        let (mi, ni, ki) = (dim(m)?, dim(n)?, dim(k)?);
+        let mut a = self.as_slice().to_vec();
+        let mut tau = vec![0.0; k];
+        let mut info = 0;
+
+        let mut lwork_query = 0.0;
+        unsafe {
+            dgeqrf_(
+                &mi,
+                &ni,
+                a.as_mut_ptr(),
+                &ld(m)?,
+                tau.as_mut_ptr(),
+                &mut lwork_query,
+                &-1,
+                &mut info,
+            );
+        }
+        check_info("dgeqrf", info)?;
+        let mut work = vec![0.0; (lwork_query as usize).max(1)];
+        unsafe {
+            dgeqrf_(
+                &mi,
+                &ni,
+                a.as_mut_ptr(),
+                &ld(m)?,
+                tau.as_mut_ptr(),
+                work.as_mut_ptr(),
+                &dim(work.len())?,
+                &mut info,
+            );
+        }
+        check_info("dgeqrf", info)?;
+
+        let mut r = Matrix::<RealVector<SEXP>>::new(k, n).protect_box();
+        let r_slice = r.inner_mut().as_mut_slice();
+        for j in 0..n {
+            for i in 0..k {
+                r_slice[j * k + i] = if i <= j { a[j * m + i] } else { 0.0 };
+            }
+        }
+
+        unsafe {
+            dorgqr_(
+                &mi,
+                &ki,
+                &ki,
+                a.as_mut_ptr(),
+                &ld(m)?,
+                tau.as_ptr(),
+                &mut lwork_query,
+                &-1,
+                &mut info,
+            );
+        }
+        check_info("dorgqr", info)?;
+        work.resize((lwork_query as usize).max(1), 0.0);
+        unsafe {
+            dorgqr_(
+                &mi,
+                &ki,
+                &ki,
+                a.as_mut_ptr(),
+                &ld(m)?,
+                tau.as_ptr(),
+                work.as_mut_ptr(),
+                &dim(work.len())?,
+                &mut info,
+            );
+        }
+        check_info("dorgqr", info)?;
+
+        let mut q = Matrix::<RealVector<SEXP>>::new(m, k).protect_box();
+        q.inner_mut().as_mut_slice().copy_from_slice(&a[..m * k]);
+
+        Ok(Qr { q, r })
+    }
+
+    /// The thin singular value decomposition through LAPACK `dgesdd`, like R's `La.svd`.
+    pub fn svd(&self) -> Result<Svd, LinalgError> {
+        let (m, n) = self.dims();
+        let k = m.min(n);
+        let (mi, ni) = (dim(m)?, dim(n)?);
+        let mut a = self.as_slice().to_vec();
+        let mut d = RealVector::new(k).protect_box();
+        let mut u = Matrix::<RealVector<SEXP>>::new(m, k).protect_box();
+        let mut vt = Matrix::<RealVector<SEXP>>::new(k, n).protect_box();
+        let mut iwork = vec![0; 8 * k];
+        let mut info = 0;
+
+        let mut lwork_query = 0.0;
+        unsafe {
+            dgesdd_(
+                fchar(b"S"),
+                &mi,
+                &ni,
+                a.as_mut_ptr(),
+                &ld(m)?,
+                d.as_mut_slice().as_mut_ptr(),
+                u.inner_mut().as_mut_slice().as_mut_ptr(),
+                &ld(m)?,
+                vt.inner_mut().as_mut_slice().as_mut_ptr(),
+                &ld(k)?,
+                &mut lwork_query,
+                &-1,
+                iwork.as_mut_ptr(),
+                &mut info,
+                1,
+            );
+        }
+        check_info("dgesdd", info)?;
+        let mut work = vec![0.0; (lwork_query as usize).max(1)];
+        unsafe {
+            dgesdd_(
+                fchar(b"S"),
+                &mi,
+                &ni,
+                a.as_mut_ptr(),
+                &ld(m)?,
+                d.as_mut_slice().as_mut_ptr(),
+                u.inner_mut().as_mut_slice().as_mut_ptr(),
+                &ld(m)?,
+                vt.inner_mut().as_mut_slice().as_mut_ptr(),
+                &ld(k)?,
+                work.as_mut_ptr(),
+                &dim(work.len())?,
+                iwork.as_mut_ptr(),
+                &mut info,
+                1,
+            );
+        }
+        check_info("dgesdd", info)?;
+
+        Ok(Svd { d, u, vt })
+    }
+}
@@ -0,0 +1,74 @@
+//! Zero-copy [`nalgebra`] views over R vectors and matrices.
+//!
+//! Both R and [`nalgebra`] store matrices in column-major order, so no striding is needed.
+use nalgebra::{DMatrixView, DMatrixViewMut, DVectorView, DVectorViewMut};
+
+use crate::{
+    sexp::{
+        matrix::Matrix,
+        vector::{IntegerVector, LogicalVector, RealVector},
+    },
+    HasSEXP, IndexableSEXP, JustSEXP, ProtectedSEXP, SEXP,
+};
+
+macro_rules! impl_nalgebra_views {
+    ($vector:ident, $elem_ty:ty) => {
+        impl<T: HasSEXP> $vector<T> {
+            #[must_use]
+            /// A [`nalgebra`] column vector view of this vector.
+            pub fn as_dvector_view(&self) -> DVectorView<'_, $elem_ty> {
+                DVectorView::from_slice(self.as_slice(), self.len())
+            }
+
+            #[must_use]
+            /// A mutable [`nalgebra`] column vector view of this vector.
+            pub fn as_dvector_view_mut(&mut self) -> DVectorViewMut<'_, $elem_ty> {
+                let len = self.len();
+                DVectorViewMut::from_slice(self.as_mut_slice(), len)
+            }
+        }
+
+        impl<T: JustSEXP> Matrix<$vector<T>> {
+            #[must_use]
+            /// A [`nalgebra`] view of this matrix.
+            pub fn as_dmatrix_view(&self) -> DMatrixView<'_, $elem_ty> {
+                DMatrixView::from_slice(self.as_slice(), self.nrows(), self.ncols())
+            }
+
+            #[must_use]
+            /// A mutable [`nalgebra`] view of this matrix.
+            pub fn as_dmatrix_view_mut(&mut self) -> DMatrixViewMut<'_, $elem_ty> {
+                let (nrow, ncol) = (self.nrows(), self.ncols());
+                DMatrixViewMut::from_slice(self.inner_mut().as_mut_slice(), nrow, ncol)
+            }
+        }
+
+        impl Matrix<$vector<SEXP>> {
+            #[must_use]
+            /// Allocate a new matrix and fill it in place through a mutable [`nalgebra`] view.
+            ///
+            /// The matrix is protected while `fill` runs.
+            pub fn from_dmatrix_view_fn(
+                nrow: usize,
+                ncol: usize,
+                fill: impl FnOnce(DMatrixViewMut<'_, $elem_ty>),
+            ) -> Self {
+                let mut matrix = Self::new(nrow, ncol).protect();
+                fill(matrix.as_dmatrix_view_mut());
+                matrix.unprotect()
+            }
+
+            #[must_use]
+            /// Allocate a new matrix with the contents of a [`nalgebra`] matrix view.
+            pub fn from_dmatrix_view(source: DMatrixView<'_, $elem_ty>) -> Self {
+                Self::from_dmatrix_view_fn(source.nrows(), source.ncols(), |mut view| {
+                    view.copy_from(&source)
+                })
+            }
+        }
+    };
+}
+
+impl_nalgebra_views!(RealVector, f64);
+impl_nalgebra_views!(IntegerVector, i32);
+impl_nalgebra_views!(LogicalVector, i32);
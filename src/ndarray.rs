@@ -0,0 +1,96 @@
+//! Zero-copy [`ndarray`] views over R vectors and arrays.
+//!
+//! R stores arrays in column-major order, so the views returned here have Fortran strides.
+use ndarray::{
+    ArrayBase, ArrayView1, ArrayView2, ArrayViewD, ArrayViewMut1, ArrayViewMut2, ArrayViewMutD,
+    Data, Ix2, IxDyn, ShapeBuilder,
+};
+
+use crate::{
+    sexp::{
+        array::Array,
+        matrix::Matrix,
+        vector::{IntegerVector, LogicalVector, RealVector},
+    },
+    HasSEXP, JustSEXP, ProtectedSEXP, SEXP,
+};
+
+macro_rules! impl_ndarray_views {
+    ($vector:ident, $elem_ty:ty) => {
+        impl<T: HasSEXP> $vector<T> {
+            #[must_use]
+            /// A one-dimensional [`ndarray`] view of this vector.
+            pub fn as_array_view(&self) -> ArrayView1<'_, $elem_ty> {
+                ArrayView1::from(self.as_slice())
+            }
+
+            #[must_use]
+            /// A mutable one-dimensional [`ndarray`] view of this vector.
+            pub fn as_array_view_mut(&mut self) -> ArrayViewMut1<'_, $elem_ty> {
+                ArrayViewMut1::from(self.as_mut_slice())
+            }
+        }
+
+        impl<T: JustSEXP> Matrix<$vector<T>> {
+            #[must_use]
+            /// A column-major [`ndarray`] view of this matrix.
+            pub fn as_array_view(&self) -> ArrayView2<'_, $elem_ty> {
+                ArrayView2::from_shape((self.nrows(), self.ncols()).f(), self.as_slice())
+                    .expect("dim attribute does not match the vector length")
+            }
+
+            #[must_use]
+            /// A mutable column-major [`ndarray`] view of this matrix.
+            pub fn as_array_view_mut(&mut self) -> ArrayViewMut2<'_, $elem_ty> {
+                let shape = (self.nrows(), self.ncols()).f();
+                ArrayViewMut2::from_shape(shape, self.inner_mut().as_mut_slice())
+                    .expect("dim attribute does not match the vector length")
+            }
+        }
+
+        impl<T: JustSEXP, const N: usize> Array<$vector<T>, N> {
+            #[must_use]
+            /// A column-major, dynamic-rank [`ndarray`] view of this array.
+            pub fn as_array_view_dyn(&self) -> ArrayViewD<'_, $elem_ty> {
+                ArrayViewD::from_shape(IxDyn(&self.shape()).f(), self.as_slice())
+                    .expect("dim attribute does not match the vector length")
+            }
+
+            #[must_use]
+            /// A mutable column-major, dynamic-rank [`ndarray`] view of this array.
+            pub fn as_array_view_mut_dyn(&mut self) -> ArrayViewMutD<'_, $elem_ty> {
+                let shape = IxDyn(&self.shape()).f();
+                ArrayViewMutD::from_shape(shape, self.inner_mut().as_mut_slice())
+                    .expect("dim attribute does not match the vector length")
+            }
+        }
+
+        impl Matrix<$vector<SEXP>> {
+            #[must_use]
+            /// Allocate a new matrix and fill it in place through a mutable [`ndarray`] view.
+            ///
+            /// The matrix is protected while `fill` runs.
+            pub fn from_array_view_fn(
+                nrow: usize,
+                ncol: usize,
+                fill: impl FnOnce(ArrayViewMut2<'_, $elem_ty>),
+            ) -> Self {
+                let mut matrix = Self::new(nrow, ncol).protect();
+                fill(matrix.as_array_view_mut());
+                matrix.unprotect()
+            }
+
+            #[must_use]
+            /// Allocate a new matrix with the contents of an [`ndarray`] array.
+            pub fn from_ndarray<S: Data<Elem = $elem_ty>>(array: &ArrayBase<S, Ix2>) -> Self {
+                Self::from_array_view_fn(array.nrows(), array.ncols(), |mut view| {
+                    view.assign(array)
+                })
+            }
+        }
+    };
+}
+
+impl_ndarray_views!(RealVector, f64);
+impl_ndarray_views!(IntegerVector, i32);
+impl_ndarray_views!(LogicalVector, i32);
@@ -1,9 +1,12 @@
 //! Common traits and types for working with this crate.
 
 pub use crate::{
+    convert::{named, FromSexp, IntoSexp},
+    interrupt::{check_interrupt, Interruptible as _},
     message::UnwrapR as _,
     null as r_nil,
     sexp::{
+        array::Array as ArraySEXP,
         matrix::Matrix as MatrixSEXP,
         vector::{
             Character as CharacterSEXP, CharacterVector as CharacterVectorSEXP,
@@ -1,19 +1,29 @@
 //! Abstractions for protecting R objects from garbage collection.
 use std::{
     fmt::{Debug, Display},
+    marker::PhantomData,
     ops::{Deref, DerefMut, Index, IndexMut},
     sync::atomic::AtomicU32,
 };
 
-use crate::{HasSEXP, IndexableSEXP, ProtectedSEXP, TypedSEXP};
+use crate::{
+    sexp::vector::{Character, CharacterVector, IntegerVector, List, LogicalVector, RealVector},
+    thread::debug_check_main_thread,
+    HasSEXP, IndexableSEXP, ProtectedSEXP, TypedSEXP,
+};
 use libR_sys::*;
 
 #[cfg(feature = "checked_protect_stack")]
-use crate::debug::PROTECT_STACK_CHECK;
+use crate::debug::{protect_stack_checked_pop, protect_stack_push};
 
 #[cfg(feature = "checked_protect_stack")]
 use std::sync::RwLock;
 
+#[cfg(feature = "leak_tracking")]
+use crate::debug::{track_handle, tracked_handles, untrack_handle};
+#[cfg(feature = "leak_tracking")]
+use std::{backtrace::Backtrace, rc::Rc};
+
 /// TODO integrate with R unwind protection stack
 
 /// Work in process: A frame of R objects on the protection stack to be unprotected together.
@@ -25,8 +35,13 @@ pub struct ProtectFrame {
 }
 
 /// An R object that is on the protection heap.
+///
+/// Like all R objects it must stay on the R main thread, so it is neither [`Send`] nor [`Sync`].
 pub struct BoxProtected<T: HasSEXP> {
     inner: Option<T>,
+    #[cfg(feature = "leak_tracking")]
+    handle: u64,
+    _marker: PhantomData<*mut ()>,
 }
 
 impl<T: HasSEXP> Deref for BoxProtected<T> {
@@ -46,11 +61,21 @@ impl<T: HasSEXP> DerefMut for BoxProtected<T> {
 impl<T: HasSEXP> BoxProtected<T> {
     /// Protect the given object in the protection heap.
     pub fn new(inner: T) -> Self {
+        debug_check_main_thread();
         unsafe {
             R_PreserveObject(inner.get_sexp());
         }
 
-        BoxProtected { inner: Some(inner) }
+        BoxProtected {
+            #[cfg(feature = "leak_tracking")]
+            handle: track_handle(
+                HandleKind::Preserved,
+                inner.get_sexp(),
+                std::any::type_name::<T>(),
+            ),
+            inner: Some(inner),
+            _marker: PhantomData,
+        }
     }
 }
 
@@ -60,7 +85,14 @@ impl<T: HasSEXP + Clone> Clone for BoxProtected<T> {
             let cloned_inner = self.inner.as_ref().unwrap().clone();
             R_PreserveObject(cloned_inner.get_sexp());
             BoxProtected {
+                #[cfg(feature = "leak_tracking")]
+                handle: track_handle(
+                    HandleKind::Preserved,
+                    cloned_inner.get_sexp(),
+                    std::any::type_name::<T>(),
+                ),
                 inner: Some(cloned_inner),
+                _marker: PhantomData,
             }
         }
     }
@@ -96,6 +128,9 @@ impl<T: TypedSEXP> TypedSEXP for BoxProtected<T> {
 unsafe impl<T: HasSEXP> ProtectedSEXP for BoxProtected<T> {
     type Inner = T;
 
+    /// Forget the handle, the object stays preserved for the rest of the session.
+    ///
+    /// With the `leak_tracking` feature, it is still reported by [`live_handles`].
     fn forget(mut self) -> T {
         self.inner.take().unwrap()
     }
@@ -105,23 +140,31 @@ unsafe impl<T: HasSEXP> ProtectedSEXP for BoxProtected<T> {
         unsafe {
             R_ReleaseObject(inner.get_sexp());
         }
+        #[cfg(feature = "leak_tracking")]
+        untrack_handle(self.handle);
         inner
     }
 }
 
 impl<T: HasSEXP> Drop for BoxProtected<T> {
     fn drop(&mut self) {
+        debug_check_main_thread();
         if let Some(inner) = self.inner.take() {
             unsafe {
                 R_ReleaseObject(inner.get_sexp());
             }
+            #[cfg(feature = "leak_tracking")]
+            untrack_handle(self.handle);
         }
     }
 }
 
 /// An R object that is on the protection stack.
+///
+/// Like all R objects it must stay on the R main thread, so it is neither [`Send`] nor [`Sync`].
 pub struct Protected<T: HasSEXP> {
     inner: Option<T>,
+    _marker: PhantomData<*mut ()>,
 }
 
 impl<T: HasSEXP + Debug> Debug for Protected<T> {
@@ -146,30 +189,44 @@ impl<T: HasSEXP> Deref for Protected<T> {
     }
 }
 
+impl<T: HasSEXP> DerefMut for Protected<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.inner.as_mut().unwrap()
+    }
+}
+
 impl<T: HasSEXP> Protected<T> {
     /// Protect the given object in the protection stack.
     ///
     /// The returned object must be dropped in the order it was created.
+    /// With the `checked_protect_stack` feature, the caller location is reported if it is not.
+    #[track_caller]
     pub fn new(inner: T) -> Self {
+        debug_check_main_thread();
         unsafe {
             Rf_protect(inner.get_sexp());
             #[cfg(feature = "checked_protect_stack")]
-            PROTECT_STACK_CHECK.push(inner.get_sexp());
+            protect_stack_push(inner.get_sexp());
         }
 
-        Protected { inner: Some(inner) }
+        Protected {
+            inner: Some(inner),
+            _marker: PhantomData,
+        }
     }
 }
 
 impl<T: HasSEXP + Clone> Clone for Protected<T> {
+    #[track_caller]
     fn clone(&self) -> Self {
         unsafe {
             let cloned_inner = self.inner.as_ref().unwrap().clone();
             Rf_protect(cloned_inner.get_sexp());
             #[cfg(feature = "checked_protect_stack")]
-            PROTECT_STACK_CHECK.push(cloned_inner.get_sexp());
+            protect_stack_push(cloned_inner.get_sexp());
             Protected {
                 inner: Some(cloned_inner),
+                _marker: PhantomData,
             }
         }
     }
@@ -177,12 +234,18 @@ impl<T: HasSEXP + Clone> Clone for Protected<T> {
 
 impl<T: HasSEXP> Drop for Protected<T> {
     fn drop(&mut self) {
+        // Already released by `unprotect` or `forget`.
+        if self.inner.is_none() {
+            return;
+        }
+        debug_check_main_thread();
+        // Unprotect first, so R's protect stack stays balanced even if the check panics.
         unsafe {
             Rf_unprotect(1);
-            #[cfg(feature = "checked_protect_stack")]
-            if let Some(inner) = self.inner.as_ref() {
-                PROTECT_STACK_CHECK.checked_pop(inner.get_sexp());
-            }
+        }
+        #[cfg(feature = "checked_protect_stack")]
+        if let Some(inner) = self.inner.as_ref() {
+            protect_stack_checked_pop(inner.get_sexp());
         }
     }
 }
@@ -229,9 +292,7 @@ unsafe impl<T: HasSEXP> ProtectedSEXP for Protected<T> {
 
     fn forget(mut self) -> T {
         #[cfg(feature = "checked_protect_stack")]
-        unsafe {
-            PROTECT_STACK_CHECK.checked_pop(self.inner.as_ref().unwrap().get_sexp())
-        };
+        protect_stack_checked_pop(self.inner.as_ref().unwrap().get_sexp());
         self.inner.take().unwrap()
     }
 
@@ -239,9 +300,9 @@ unsafe impl<T: HasSEXP> ProtectedSEXP for Protected<T> {
         let inner = self.inner.take().unwrap();
         unsafe {
             Rf_unprotect(1);
-            #[cfg(feature = "checked_protect_stack")]
-            PROTECT_STACK_CHECK.checked_pop(inner.get_sexp());
         }
+        #[cfg(feature = "checked_protect_stack")]
+        protect_stack_checked_pop(inner.get_sexp());
         inner
     }
 }
@@ -249,3 +310,247 @@ unsafe impl<T: HasSEXP> ProtectedSEXP for Protected<T> {
 impl<T: TypedSEXP> TypedSEXP for Protected<T> {
     const SEXP_TYPE: SEXPTYPE = T::SEXP_TYPE;
 }
+
+/// Invariant lifetime brand of a [`Scope`], so values from different scopes can't be mixed up.
+type Brand<'s> = PhantomData<fn(&'s ()) -> &'s ()>;
+
+/// A protection scope created by [`scope`], everything allocated through it stays protected until the scope ends.
+pub struct Scope<'s> {
+    /// A protected pairlist holding the values protected in this scope in its tail.
+    head: SEXP,
+    _brand: Brand<'s>,
+}
+
+impl<'s> Scope<'s> {
+    /// Protect a value until the end of the scope.
+    ///
+    /// Unlike [`Protected`], values can be dropped in any order.
+    pub fn protect<T: HasSEXP>(&self, value: T) -> Scoped<'s, T> {
+        debug_check_main_thread();
+        unsafe {
+            Rf_protect(value.get_sexp());
+            SETCDR(self.head, Rf_cons(value.get_sexp(), CDR(self.head)));
+            Rf_unprotect(1);
+        }
+        Scoped {
+            inner: value,
+            _brand: PhantomData,
+        }
+    }
+
+    /// Allocate a protected numeric vector of length `len`.
+    pub fn alloc_real(&self, len: usize) -> Scoped<'s, RealVector<SEXP>> {
+        self.protect(RealVector::new(len))
+    }
+
+    /// Allocate a protected integer vector of length `len`.
+    pub fn alloc_integer(&self, len: usize) -> Scoped<'s, IntegerVector<SEXP>> {
+        self.protect(IntegerVector::new(len))
+    }
+
+    /// Allocate a protected logical vector of length `len`.
+    pub fn alloc_logical(&self, len: usize) -> Scoped<'s, LogicalVector<SEXP>> {
+        self.protect(LogicalVector::new(len))
+    }
+
+    /// Allocate a protected character vector of length `len`.
+    pub fn alloc_character(&self, len: usize) -> Scoped<'s, CharacterVector<SEXP>> {
+        self.protect(CharacterVector::new(len))
+    }
+
+    /// Allocate a protected list of length `len`.
+    pub fn alloc_list(&self, len: usize) -> Scoped<'s, List<SEXP>> {
+        self.protect(List::new(len))
+    }
+}
+
+/// A value protected by a [`Scope`], branded with the scope's lifetime so it can't outlive it.
+///
+/// The only way out of the scope is as its return value, see [`scope`]. It does not deref to the wrapped value,
+/// which could be copied out of the scope, the accessors are forwarded instead.
+/// The raw [`SEXP`] from [`HasSEXP::get_sexp`] is not branded and must not be used after the scope ends.
+pub struct Scoped<'s, T: HasSEXP> {
+    inner: T,
+    _brand: Brand<'s>,
+}
+
+impl<T: HasSEXP + Debug> Debug for Scoped<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Scoped").field(&self.inner).finish()
+    }
+}
+
+/// Forward the slice accessors of a vector type, since [`Scoped`] does not deref to the unbranded value.
+macro_rules! impl_scoped_slice {
+    ($struct:ident, $elem_ty:ty) => {
+        impl<T: HasSEXP> Scoped<'_, $struct<T>> {
+            #[must_use]
+            /// Get a slice of the elements in this vector.
+            pub fn as_slice(&self) -> &[$elem_ty] {
+                self.inner.as_slice()
+            }
+
+            #[must_use]
+            /// Get a mutable slice of the elements in this vector.
+            pub fn as_mut_slice(&mut self) -> &mut [$elem_ty] {
+                self.inner.as_mut_slice()
+            }
+        }
+    };
+}
+
+impl_scoped_slice!(RealVector, f64);
+impl_scoped_slice!(IntegerVector, i32);
+impl_scoped_slice!(LogicalVector, i32);
+
+impl<T: HasSEXP> Scoped<'_, CharacterVector<T>> {
+    /// Set the string at the given index.
+    pub fn set_str(&mut self, index: usize, value: impl Into<Character>) {
+        self.inner.set_str(index, value);
+    }
+}
+
+impl<T: HasSEXP> HasSEXP for Scoped<'_, T> {
+    fn get_sexp(&self) -> SEXP {
+        self.inner.get_sexp()
+    }
+}
+
+impl<T: IndexableSEXP> IndexableSEXP for Scoped<'_, T> {
+    type Index = T::Index;
+    type Output = T::Output;
+
+    fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    fn get_elt(&self, index: Self::Index) -> Self::Output {
+        self.inner.get_elt(index)
+    }
+
+    fn set_elt(&mut self, index: Self::Index, value: impl Into<Self::Output>) {
+        self.inner.set_elt(index, value);
+    }
+}
+
+impl<Idx, T: IndexableSEXP + Index<Idx>> Index<Idx> for Scoped<'_, T> {
+    type Output = <T as Index<Idx>>::Output;
+
+    fn index(&self, index: Idx) -> &Self::Output {
+        &self.inner[index]
+    }
+}
+
+impl<Idx, T: IndexableSEXP + IndexMut<Idx>> IndexMut<Idx> for Scoped<'_, T> {
+    fn index_mut(&mut self, index: Idx) -> &mut Self::Output {
+        &mut self.inner[index]
+    }
+}
+
+impl<T: TypedSEXP> TypedSEXP for Scoped<'_, T> {
+    const SEXP_TYPE: SEXPTYPE = T::SEXP_TYPE;
+}
+
+/// Run `f` in a new protection [`Scope`] and return the SEXP of the value it returns.
+///
+/// Values allocated or protected through the scope carry its lifetime, so the compiler rejects
+/// using them after the scope ended or storing them outside of it. The returned SEXP is no longer protected,
+/// so it should be returned to R right away, e.g. from a `.Call` entry point.
+///
+/// Storing a value outside of the scope does not compile:
+///
+/// ```compile_fail,E0521
+/// use typed_sexp::{
+///     protect::{scope, Scoped},
+///     sexp::vector::RealVector,
+///     SEXP,
+/// };
+///
+/// let mut escaped: Option<Scoped<'_, RealVector<SEXP>>> = None;
+/// scope(|s| {
+///     escaped = Some(s.alloc_real(1));
+///     s.alloc_real(1)
+/// });
+/// ```
+pub fn scope<T, F>(f: F) -> SEXP
+where
+    T: HasSEXP,
+    F: for<'s> FnOnce(&Scope<'s>) -> Scoped<'s, T>,
+{
+    let head = Protected::new(unsafe { Rf_cons(R_NilValue, R_NilValue) });
+    let scope = Scope {
+        head: head.get_sexp(),
+        _brand: PhantomData,
+    };
+    let result = f(&scope).get_sexp();
+    drop(head);
+    result
+}
+
+/// What keeps a [`LiveHandle`] alive.
+#[cfg(feature = "leak_tracking")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandleKind {
+    /// A [`BoxProtected`] handle, including ones that were [`forget`](ProtectedSEXP::forget)-ed.
+    Preserved,
+    /// A boxed external pointer created with [`Ptr::wrap_typed`](crate::sexp::ptr::Ptr::wrap_typed) whose finalizer did not run yet.
+    ExternalPtr,
+}
+
+/// An R object kept alive by a handle that was not released yet, see [`live_handles`].
+#[cfg(feature = "leak_tracking")]
+#[derive(Debug, Clone)]
+pub struct LiveHandle {
+    pub(crate) id: u64,
+    /// What keeps the object alive.
+    pub kind: HandleKind,
+    /// The object.
+    pub sexp: SEXP,
+    /// The Rust type of the handle contents, the wrapper type or the boxed type of an external pointer.
+    pub type_name: &'static str,
+    /// Where the handle was created.
+    pub backtrace: Rc<Backtrace>,
+}
+
+#[cfg(feature = "leak_tracking")]
+impl Display for LiveHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{:?} {} at {:?}, created at:",
+            self.kind, self.type_name, self.sexp
+        )?;
+        write!(f, "{}", self.backtrace)
+    }
+}
+
+/// All [`BoxProtected`] handles and boxed external pointers that still keep their objects alive, oldest first.
+///
+/// Long-lived entries here are usually leaks: forgotten handles, or external pointers that are still referenced from R.
+#[cfg(feature = "leak_tracking")]
+pub fn live_handles() -> Vec<LiveHandle> {
+    tracked_handles()
+}
+
+/// A human-readable report of [`live_handles`].
+#[cfg(feature = "leak_tracking")]
+pub fn leak_report() -> String {
+    let handles = live_handles();
+    let mut report = format!("{} live protected handle(s)", handles.len());
+    for handle in &handles {
+        report.push_str("\n\n");
+        report.push_str(&handle.to_string());
+    }
+    report
+}
+
+/// R entry point printing a [`leak_report`] as a message, call it with `.Call("typed_sexp_leak_report")`.
+///
+/// Returns the number of live handles.
+#[cfg(feature = "leak_tracking")]
+#[export_name = "typed_sexp_leak_report"]
+pub extern "C" fn leak_report_call() -> SEXP {
+    let count = live_handles().len();
+    crate::message::r_message(&leak_report());
+    unsafe { Rf_ScalarInteger(count as i32) }
+}
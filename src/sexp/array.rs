@@ -0,0 +1,448 @@
+use std::{
+    fmt::{Debug, Display},
+    ops::{Index, IndexMut},
+};
+
+use derive_more::Deref;
+use libR_sys::{SEXPTYPE::*, *};
+
+use crate::{DowncastSEXP, HasSEXP, IndexableSEXP, JustSEXP, TypedSEXP};
+
+/// Get the `dim` attribute of a SEXP, if it has one.
+///
+/// # Safety
+///
+/// The returned slice borrows the attribute, it must not outlive the SEXP.
+unsafe fn sexp_dim<'a>(sexp: SEXP) -> Option<&'a [i32]> {
+    let dim = Rf_getAttrib(sexp, R_DimSymbol);
+    if TYPEOF(dim) == INTSXP {
+        Some(std::slice::from_raw_parts(
+            INTEGER(dim),
+            Rf_xlength(dim) as usize,
+        ))
+    } else {
+        None
+    }
+}
+
+/// Column-major strides for the given shape.
+fn column_major_strides(shape: &[usize]) -> Vec<usize> {
+    let mut stride = 1;
+    shape
+        .iter()
+        .map(|&dim| {
+            let ret = stride;
+            stride *= dim;
+            ret
+        })
+        .collect()
+}
+
+/// An array extent that does not fit in the 32-bit integers of R's `dim` attribute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DimensionTooLarge {
+    /// The dimension that is too large.
+    pub axis: usize,
+    /// The requested extent of that dimension.
+    pub extent: usize,
+}
+
+impl Display for DimensionTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "dimension {} has extent {}, but R dimensions must be at most {}",
+            self.axis,
+            self.extent,
+            i32::MAX
+        )
+    }
+}
+
+impl std::error::Error for DimensionTooLarge {}
+
+#[repr(transparent)]
+#[derive(Deref)]
+/// A wrapper around an R array of a given type with exactly `N` dimensions.
+///
+/// Elements are stored in column-major order, like R does.
+pub struct Array<U: JustSEXP + TypedSEXP + IndexableSEXP, const N: usize> {
+    array: U,
+}
+
+impl<U: JustSEXP + TypedSEXP + IndexableSEXP, const N: usize> Debug for Array<U, N> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let shape = self.shape();
+        if N == 2 {
+            write!(f, "Matrix ({:?}, {}x{})", U::SEXP_TYPE, shape[0], shape[1])
+        } else {
+            write!(f, "Array ({:?}, {:?})", U::SEXP_TYPE, shape)
+        }
+    }
+}
+
+impl<U: JustSEXP + TypedSEXP + IndexableSEXP, const N: usize> Array<U, N> {
+    /// Create a new array with the given shape.
+    ///
+    /// Fails if an extent does not fit in R's integer `dim` attribute.
+    pub fn with_shape(shape: [usize; N]) -> Result<Self, DimensionTooLarge>
+    where
+        U: JustSEXP<Inner = SEXP>,
+    {
+        let mut dims = [0; N];
+        for (axis, (&extent, dim)) in shape.iter().zip(dims.iter_mut()).enumerate() {
+            *dim = i32::try_from(extent).map_err(|_| DimensionTooLarge { axis, extent })?;
+        }
+        unsafe {
+            let dim = Rf_protect(Rf_allocVector(INTSXP, N as R_xlen_t));
+            for (i, &d) in dims.iter().enumerate() {
+                SET_INTEGER_ELT(dim, i as R_xlen_t, d);
+            }
+            let sexp = Rf_allocArray(U::SEXP_TYPE, dim);
+            Rf_unprotect(1);
+            Ok(Array {
+                array: U::wrap_sexp_unchecked(sexp),
+            })
+        }
+    }
+
+    #[must_use]
+    /// The extent of each dimension of the array.
+    pub fn shape(&self) -> [usize; N] {
+        let dim = unsafe { sexp_dim(self.array.get_sexp()) }.unwrap_or_default();
+        std::array::from_fn(|i| dim.get(i).copied().unwrap_or_default() as usize)
+    }
+
+    #[must_use]
+    /// The column-major strides of the array, in elements.
+    pub fn strides(&self) -> [usize; N] {
+        let strides = column_major_strides(&self.shape());
+        std::array::from_fn(|i| strides[i])
+    }
+
+    /// The linear offset of the given index into the underlying vector.
+    ///
+    /// Panics if any component of the index is out of bounds.
+    pub fn offset_of(&self, index: [usize; N]) -> usize {
+        let shape = self.shape();
+        let mut offset = 0;
+        let mut stride = 1;
+        for (&i, &dim) in index.iter().zip(shape.iter()) {
+            if i >= dim {
+                panic!(
+                    "index out of bounds: the shape is {:?} but the index is {:?}",
+                    shape, index
+                );
+            }
+            offset += i * stride;
+            stride *= dim;
+        }
+        offset
+    }
+
+    /// Get a mutable reference to the underlying vector, e.g. to fill it through a slice.
+    pub fn inner_mut(&mut self) -> &mut U {
+        &mut self.array
+    }
+}
+
+impl<U, const N: usize> Array<U, N>
+where
+    U: JustSEXP + TypedSEXP + IndexableSEXP<Index = usize>,
+{
+    /// A view of the whole array.
+    pub fn view(&self) -> ArrayView<'_, U> {
+        ArrayView {
+            array: &self.array,
+            layout: Layout::new(&self.shape()),
+        }
+    }
+
+    /// A mutable view of the whole array.
+    pub fn view_mut(&mut self) -> ArrayViewMut<'_, U> {
+        let layout = Layout::new(&self.shape());
+        ArrayViewMut {
+            array: &mut self.array,
+            layout,
+        }
+    }
+
+    /// A view of the sub-array at `index` along `axis`, with `axis` removed.
+    ///
+    /// For example slicing a 3-D image stack along axis 2 gives a single image.
+    pub fn slice_axis(&self, axis: usize, index: usize) -> ArrayView<'_, U> {
+        self.view().slice_axis(axis, index)
+    }
+
+    /// A mutable view of the sub-array at `index` along `axis`, with `axis` removed.
+    pub fn slice_axis_mut(&mut self, axis: usize, index: usize) -> ArrayViewMut<'_, U> {
+        self.view_mut().slice_axis(axis, index)
+    }
+}
+
+impl<U, const N: usize> HasSEXP for Array<U, N>
+where
+    U: JustSEXP + TypedSEXP + IndexableSEXP<Index = usize>,
+{
+    fn get_sexp(&self) -> SEXP {
+        self.array.get_sexp()
+    }
+}
+
+unsafe impl<U, const N: usize> JustSEXP for Array<U, N>
+where
+    U: JustSEXP + TypedSEXP + IndexableSEXP<Index = usize>,
+{
+    type Inner = U::Inner;
+
+    fn upcast(self) -> Self::Inner {
+        self.array.upcast()
+    }
+
+    fn inner_ref(&self) -> &Self::Inner {
+        self.array.inner_ref()
+    }
+
+    fn wrap_sexp(sexp: SEXP) -> Option<Self> {
+        if unsafe { sexp_dim(sexp) }.is_some_and(|dim| dim.len() == N) {
+            U::wrap_sexp(sexp).map(|inner| Array { array: inner })
+        } else {
+            None
+        }
+    }
+
+    unsafe fn wrap_sexp_unchecked(sexp: SEXP) -> Self {
+        Array {
+            array: U::wrap_sexp_unchecked(sexp),
+        }
+    }
+}
+
+impl<U, const N: usize> IndexableSEXP for Array<U, N>
+where
+    U: JustSEXP + TypedSEXP + IndexableSEXP<Index = usize>,
+{
+    type Index = [usize; N];
+    type Output = U::Output;
+
+    fn len(&self) -> usize {
+        self.shape().iter().product()
+    }
+
+    fn get_elt(&self, index: Self::Index) -> Self::Output {
+        self.array.get_elt(self.offset_of(index))
+    }
+
+    fn set_elt(&mut self, index: Self::Index, value: impl Into<Self::Output>) {
+        let offset = self.offset_of(index);
+        self.array.set_elt(offset, value);
+    }
+}
+
+impl<U, const N: usize> Index<[usize; N]> for Array<U, N>
+where
+    U: JustSEXP + TypedSEXP + IndexableSEXP<Index = usize> + Index<usize>,
+{
+    type Output = <U as Index<usize>>::Output;
+
+    fn index(&self, index: [usize; N]) -> &Self::Output {
+        &self.array[self.offset_of(index)]
+    }
+}
+
+impl<U, const N: usize> IndexMut<[usize; N]> for Array<U, N>
+where
+    U: JustSEXP + TypedSEXP + IndexableSEXP<Index = usize> + IndexMut<usize>,
+{
+    fn index_mut(&mut self, index: [usize; N]) -> &mut Self::Output {
+        let offset = self.offset_of(index);
+        &mut self.array[offset]
+    }
+}
+
+impl<U, const N: usize> DowncastSEXP<Array<U, N>> for U
+where
+    U: JustSEXP + TypedSEXP + IndexableSEXP<Index = usize>,
+{
+    fn downcast(self) -> Option<Array<U, N>> {
+        if unsafe { sexp_dim(self.get_sexp()) }.is_some_and(|dim| dim.len() == N) {
+            Some(Array { array: self })
+        } else {
+            None
+        }
+    }
+}
+
+/// The shape, strides and offset of a strided view into a vector.
+#[derive(Debug, Clone)]
+struct Layout {
+    offset: usize,
+    shape: Vec<usize>,
+    strides: Vec<usize>,
+}
+
+impl Layout {
+    fn new(shape: &[usize]) -> Self {
+        Layout {
+            offset: 0,
+            shape: shape.to_vec(),
+            strides: column_major_strides(shape),
+        }
+    }
+
+    fn offset_of(&self, index: &[usize]) -> usize {
+        if index.len() != self.shape.len()
+            || index.iter().zip(self.shape.iter()).any(|(i, d)| i >= d)
+        {
+            panic!(
+                "index out of bounds: the shape is {:?} but the index is {:?}",
+                self.shape, index
+            );
+        }
+        self.offset
+            + index
+                .iter()
+                .zip(self.strides.iter())
+                .map(|(i, s)| i * s)
+                .sum::<usize>()
+    }
+
+    fn slice_axis(mut self, axis: usize, index: usize) -> Self {
+        if axis >= self.shape.len() {
+            panic!(
+                "axis out of bounds: the view has {} dimensions but the axis is {}",
+                self.shape.len(),
+                axis
+            );
+        }
+        if index >= self.shape[axis] {
+            panic!(
+                "index out of bounds: the extent of axis {} is {} but the index is {}",
+                axis, self.shape[axis], index
+            );
+        }
+        self.offset += index * self.strides[axis];
+        self.shape.remove(axis);
+        self.strides.remove(axis);
+        self
+    }
+}
+
+/// A strided, read-only view into an [`Array`].
+pub struct ArrayView<'a, U: IndexableSEXP<Index = usize>> {
+    array: &'a U,
+    layout: Layout,
+}
+
+impl<'a, U: IndexableSEXP<Index = usize>> ArrayView<'a, U> {
+    #[must_use]
+    /// The extent of each dimension of the view.
+    pub fn shape(&self) -> &[usize] {
+        &self.layout.shape
+    }
+
+    #[must_use]
+    /// The number of dimensions of the view.
+    pub fn ndim(&self) -> usize {
+        self.layout.shape.len()
+    }
+
+    #[must_use]
+    /// The number of elements in the view.
+    pub fn len(&self) -> usize {
+        self.layout.shape.iter().product()
+    }
+
+    #[must_use]
+    /// Check if the view has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the element at the given index.
+    pub fn get(&self, index: &[usize]) -> U::Output {
+        self.array.get_elt(self.layout.offset_of(index))
+    }
+
+    /// Further slice the view at `index` along `axis`, with `axis` removed.
+    pub fn slice_axis(&self, axis: usize, index: usize) -> ArrayView<'a, U> {
+        ArrayView {
+            array: self.array,
+            layout: self.layout.clone().slice_axis(axis, index),
+        }
+    }
+}
+
+impl<U: IndexableSEXP<Index = usize> + Index<usize>> Index<&[usize]> for ArrayView<'_, U> {
+    type Output = <U as Index<usize>>::Output;
+
+    fn index(&self, index: &[usize]) -> &Self::Output {
+        &self.array[self.layout.offset_of(index)]
+    }
+}
+
+/// A strided, mutable view into an [`Array`].
+pub struct ArrayViewMut<'a, U: IndexableSEXP<Index = usize>> {
+    array: &'a mut U,
+    layout: Layout,
+}
+
+impl<'a, U: IndexableSEXP<Index = usize>> ArrayViewMut<'a, U> {
+    #[must_use]
+    /// The extent of each dimension of the view.
+    pub fn shape(&self) -> &[usize] {
+        &self.layout.shape
+    }
+
+    #[must_use]
+    /// The number of dimensions of the view.
+    pub fn ndim(&self) -> usize {
+        self.layout.shape.len()
+    }
+
+    #[must_use]
+    /// The number of elements in the view.
+    pub fn len(&self) -> usize {
+        self.layout.shape.iter().product()
+    }
+
+    #[must_use]
+    /// Check if the view has no elements.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the element at the given index.
+    pub fn get(&self, index: &[usize]) -> U::Output {
+        self.array.get_elt(self.layout.offset_of(index))
+    }
+
+    /// Set the element at the given index.
+    pub fn set(&mut self, index: &[usize], value: impl Into<U::Output>) {
+        let offset = self.layout.offset_of(index);
+        self.array.set_elt(offset, value);
+    }
+
+    /// Further slice the view at `index` along `axis`, with `axis` removed.
+    pub fn slice_axis(self, axis: usize, index: usize) -> ArrayViewMut<'a, U> {
+        ArrayViewMut {
+            array: self.array,
+            layout: self.layout.slice_axis(axis, index),
+        }
+    }
+}
+
+impl<U: IndexableSEXP<Index = usize> + Index<usize>> Index<&[usize]> for ArrayViewMut<'_, U> {
+    type Output = <U as Index<usize>>::Output;
+
+    fn index(&self, index: &[usize]) -> &Self::Output {
+        &self.array[self.layout.offset_of(index)]
+    }
+}
+
+impl<U: IndexableSEXP<Index = usize> + IndexMut<usize>> IndexMut<&[usize]> for ArrayViewMut<'_, U> {
+    fn index_mut(&mut self, index: &[usize]) -> &mut Self::Output {
+        let offset = self.layout.offset_of(index);
+        &mut self.array[offset]
+    }
+}
@@ -1,12 +1,14 @@
 use std::{
     ffi::{CStr, CString},
-    fmt::Debug,
+    fmt::{Debug, Display},
 };
 
-use crate::{prelude::*, DowncastSEXP, ProtectedSEXP};
+use crate::{prelude::*, protect::BoxProtected, DowncastSEXP, ProtectedSEXP};
 use derive_more::Deref;
 use libR_sys::*;
 
+use super::{function::Callable, promise::force_if_promise};
+
 #[derive(Deref)]
 #[repr(transparent)]
 /// A wrapper around a symbol object.
@@ -14,9 +16,15 @@ pub struct Symbol<T: JustSEXP> {
     sexp: T,
 }
 
-impl<T: JustSEXP + Debug> Debug for Symbol<T> {
+impl<T: JustSEXP> Debug for Symbol<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Symbol({:?})", self.sexp)
+        match crate::deparse_for_debug(self) {
+            Some(code) => f
+                .debug_tuple("Symbol")
+                .field(&format_args!("{}", code))
+                .finish(),
+            None => f.debug_tuple("Symbol").field(&self.get_sexp()).finish(),
+        }
     }
 }
 
@@ -35,6 +43,14 @@ impl Symbol<SEXP> {
     }
 }
 
+impl<T: JustSEXP> Symbol<T> {
+    /// The name of the symbol.
+    pub fn name(&self) -> String {
+        let name = unsafe { CharacterSEXP::wrap_sexp_unchecked(PRINTNAME(self.get_sexp())) };
+        name.as_str().unwrap_or_default().to_string()
+    }
+}
+
 impl<T: JustSEXP> HasSEXP for Symbol<T> {
     fn get_sexp(&self) -> SEXP {
         self.sexp.get_sexp()
@@ -146,7 +162,26 @@ impl<T: JustSEXP> Env<T> {
     pub fn peek<S: JustSEXP>(&self, symbol: Symbol<S>) -> Option<SEXP> {
         unsafe {
             let sexp = Rf_findVarInFrame(self.get_sexp(), symbol.get_sexp());
-            if sexp.is_null() {
+            if sexp.is_null() || sexp == R_UnboundValue {
+                None
+            } else {
+                Some(sexp)
+            }
+        }
+    }
+
+    /// Peek at a symbol in the environment, forcing the value if it is a promise.
+    ///
+    /// Returns [`None`] if the symbol is unbound or forcing the promise fails.
+    pub fn peek_forced<S: JustSEXP>(&self, symbol: Symbol<S>) -> Option<SEXP> {
+        force_if_promise(self.peek(symbol)?)
+    }
+
+    /// Look up a symbol in the environment and its parents, like `get(name, envir = env)`.
+    pub fn get_inherited<S: JustSEXP>(&self, symbol: Symbol<S>) -> Option<SEXP> {
+        unsafe {
+            let sexp = Rf_findVar(symbol.get_sexp(), self.get_sexp());
+            if sexp.is_null() || sexp == R_UnboundValue {
                 None
             } else {
                 Some(sexp)
@@ -154,11 +189,214 @@ impl<T: JustSEXP> Env<T> {
         }
     }
 
+    /// Look up a symbol in the environment and its parents, forcing the value if it is a promise.
+    ///
+    /// Returns [`None`] if the symbol is unbound or forcing the promise fails.
+    pub fn get_inherited_forced<S: JustSEXP>(&self, symbol: Symbol<S>) -> Option<SEXP> {
+        force_if_promise(self.get_inherited(symbol)?)
+    }
+
+    /// Check if the symbol is bound in this environment, without looking at parents.
+    pub fn contains<S: JustSEXP>(&self, symbol: &Symbol<S>) -> bool {
+        unsafe { R_existsVarInFrame(self.get_sexp(), symbol.get_sexp()).into() }
+    }
+
     /// Assign a value to a symbol in the environment.
-    pub fn assign<S: JustSEXP>(&mut self, symbol: Symbol<S>, value: impl HasSEXP) {
+    ///
+    /// Assigning to a locked binding, or creating a new binding in a locked environment, returns an error instead of raising an R error.
+    /// Assigning to an active binding calls its function with the value.
+    pub fn assign<S: JustSEXP>(
+        &mut self,
+        symbol: Symbol<S>,
+        value: impl HasSEXP,
+    ) -> Result<(), EnvError> {
+        if self.contains(&symbol) {
+            if self.binding_is_locked(&symbol) {
+                return Err(EnvError::LockedBinding);
+            }
+        } else if self.is_locked() {
+            return Err(EnvError::LockedEnvironment);
+        }
         unsafe {
             Rf_defineVar(symbol.get_sexp(), value.get_sexp(), self.get_sexp());
         }
+        Ok(())
+    }
+
+    /// Bind a symbol to a function that is called every time the symbol is accessed, like `makeActiveBinding`.
+    ///
+    /// The function is called with no arguments to get the value, and with the new value to assign to it.
+    pub fn make_active_binding<S: JustSEXP, F: JustSEXP>(
+        &mut self,
+        symbol: Symbol<S>,
+        function: Callable<F>,
+    ) -> Result<(), EnvError> {
+        if self.contains(&symbol) {
+            if !self.binding_is_active(&symbol) {
+                return Err(EnvError::NotActive);
+            }
+            if self.binding_is_locked(&symbol) {
+                return Err(EnvError::LockedBinding);
+            }
+        } else if self.is_locked() {
+            return Err(EnvError::LockedEnvironment);
+        }
+        unsafe {
+            R_MakeActiveBinding(symbol.get_sexp(), function.get_sexp(), self.get_sexp());
+        }
+        Ok(())
+    }
+
+    /// Lock the environment so no bindings can be added or removed, like `lockEnvironment`.
+    ///
+    /// If `bindings` is set, all existing bindings are locked as well.
+    pub fn lock(&mut self, bindings: bool) {
+        unsafe {
+            R_LockEnvironment(self.get_sexp(), bindings.into());
+        }
+    }
+
+    /// Check if the environment is locked.
+    pub fn is_locked(&self) -> bool {
+        unsafe { R_EnvironmentIsLocked(self.get_sexp()).into() }
+    }
+
+    /// Lock a binding so its value cannot be changed, like `lockBinding`.
+    pub fn lock_binding<S: JustSEXP>(&mut self, symbol: Symbol<S>) -> Result<(), EnvError> {
+        if !self.contains(&symbol) {
+            return Err(EnvError::Unbound);
+        }
+        unsafe {
+            R_LockBinding(symbol.get_sexp(), self.get_sexp());
+        }
+        Ok(())
+    }
+
+    /// Unlock a binding, like `unlockBinding`.
+    pub fn unlock_binding<S: JustSEXP>(&mut self, symbol: Symbol<S>) -> Result<(), EnvError> {
+        if !self.contains(&symbol) {
+            return Err(EnvError::Unbound);
+        }
+        unsafe {
+            R_unLockBinding(symbol.get_sexp(), self.get_sexp());
+        }
+        Ok(())
+    }
+
+    /// Check if a binding in this environment is locked. Unbound symbols are not locked.
+    pub fn binding_is_locked<S: JustSEXP>(&self, symbol: &Symbol<S>) -> bool {
+        self.contains(symbol)
+            && unsafe { R_BindingIsLocked(symbol.get_sexp(), self.get_sexp()).into() }
+    }
+
+    /// Check if a binding in this environment is an active binding. Unbound symbols are not active.
+    pub fn binding_is_active<S: JustSEXP>(&self, symbol: &Symbol<S>) -> bool {
+        self.contains(symbol)
+            && unsafe { R_BindingIsActive(symbol.get_sexp(), self.get_sexp()).into() }
+    }
+
+    /// Remove a binding from the environment, returning whether it existed.
+    pub fn remove<S: JustSEXP>(&mut self, symbol: Symbol<S>) -> bool {
+        if !self.contains(&symbol) {
+            return false;
+        }
+        unsafe {
+            R_removeVarFromFrame(symbol.get_sexp(), self.get_sexp());
+        }
+        true
+    }
+
+    /// The enclosing environment, or [`None`] for the empty environment.
+    pub fn parent(&self) -> Option<Env<SEXP>> {
+        Env::wrap_sexp(unsafe { ENCLOS(self.get_sexp()) })
+    }
+
+    /// The names bound in this environment, like `ls(env, all.names, sorted)`.
+    pub fn names(&self, all_names: bool, sorted: bool) -> CharacterVectorSEXP<SEXP> {
+        unsafe {
+            CharacterVectorSEXP::wrap_sexp_unchecked(R_lsInternal3(
+                self.get_sexp(),
+                all_names.into(),
+                sorted.into(),
+            ))
+        }
+    }
+
+    /// Iterate over the bindings in this environment, including hidden names.
+    ///
+    /// Bindings whose value is an unforced promise are forced if `force_promises` is set, otherwise they are skipped.
+    /// Promises that fail to evaluate are skipped as well.
+    pub fn bindings(&self, force_promises: bool) -> Bindings<'_, T> {
+        Bindings {
+            env: self,
+            names: self.names(true, false).protect_box(),
+            index: 0,
+            force_promises,
+        }
+    }
+}
+
+/// Errors from modifying an environment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EnvError {
+    /// The binding is locked.
+    LockedBinding,
+    /// The environment is locked, so no bindings can be added.
+    LockedEnvironment,
+    /// The symbol is not bound in the environment.
+    Unbound,
+    /// The symbol is bound to a regular value, so it cannot be made active.
+    NotActive,
+}
+
+impl Display for EnvError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvError::LockedBinding => write!(f, "cannot change value of locked binding"),
+            EnvError::LockedEnvironment => {
+                write!(f, "cannot add bindings to a locked environment")
+            }
+            EnvError::Unbound => write!(f, "no binding for symbol"),
+            EnvError::NotActive => write!(f, "symbol already has a regular binding"),
+        }
+    }
+}
+
+impl std::error::Error for EnvError {}
+
+/// An iterator over the bindings of an environment, created by [`Env::bindings`].
+pub struct Bindings<'a, T: JustSEXP> {
+    env: &'a Env<T>,
+    names: BoxProtected<CharacterVectorSEXP<SEXP>>,
+    index: usize,
+    force_promises: bool,
+}
+
+impl<T: JustSEXP> Iterator for Bindings<'_, T> {
+    type Item = (Symbol<SEXP>, SEXP);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.index < self.names.len() {
+            let name = self.names.get_elt(self.index);
+            self.index += 1;
+            unsafe {
+                let symbol = Symbol::<SEXP>::wrap_sexp_unchecked(Rf_installChar(name.get_sexp()));
+                let value = Rf_findVarInFrame(self.env.get_sexp(), symbol.get_sexp());
+                if value == R_UnboundValue {
+                    continue;
+                }
+                if TYPEOF(value) != SEXPTYPE::PROMSXP {
+                    return Some((symbol, value));
+                }
+                if !self.force_promises {
+                    continue;
+                }
+                if let Some(forced) = force_if_promise(value) {
+                    return Some((symbol, forced));
+                }
+            }
+        }
+        None
     }
 }
 
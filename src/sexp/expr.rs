@@ -0,0 +1,274 @@
+use std::ops::{Add, BitAnd, BitOr, Div, Mul, Neg, Not, Sub};
+
+use crate::{
+    convert::IntoSexp, prelude::*, protect::Protected, DEPARSE_DEFAULT_OPTIONS,
+    DEPARSE_DEFAULT_WIDTH,
+};
+use libR_sys::*;
+
+use super::{env::Symbol, function::CallError, lang::Lang};
+
+/// An R expression built on the Rust side, converted to a language object with [`Expr::to_sexp`].
+///
+/// Nothing is allocated in R until the expression is converted,
+/// except for values spliced in with [`splice`], which must be kept protected by the caller.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// A symbol, like `x`.
+    Symbol(String),
+    /// A call, with optionally named arguments.
+    Call {
+        /// The function being called, usually a symbol.
+        function: Box<Expr>,
+        /// The arguments, with their names.
+        args: Vec<(Option<String>, Expr)>,
+    },
+    /// A double scalar.
+    Real(f64),
+    /// An integer scalar.
+    Integer(i32),
+    /// A logical scalar.
+    Logical(bool),
+    /// A string scalar.
+    String(String),
+    /// `NULL`.
+    Null,
+    /// An empty argument, like in `x[, 1]`.
+    Missing,
+    /// An existing object spliced into the expression, like `!!value` in rlang.
+    Value(SEXP),
+}
+
+/// A symbol, like `quote(x)`.
+pub fn sym(name: &str) -> Expr {
+    Expr::Symbol(name.to_string())
+}
+
+/// A literal value, like `1`, `TRUE` or `"a"`.
+pub fn lit(value: impl Into<Expr>) -> Expr {
+    value.into()
+}
+
+/// An empty argument, like in `x[, 1]`.
+pub fn missing() -> Expr {
+    Expr::Missing
+}
+
+/// Splice an existing object into the expression, like `!!value` in rlang.
+///
+/// The object is not protected by the expression.
+pub fn splice(value: &impl HasSEXP) -> Expr {
+    Expr::Value(value.get_sexp())
+}
+
+/// A call to a function with positional arguments, like `fun(a, b)`.
+///
+/// Named arguments can be added with [`Expr::named`].
+pub fn call(function: impl Into<Expr>, args: impl IntoIterator<Item = Expr>) -> Expr {
+    Expr::Call {
+        function: Box::new(function.into()),
+        args: args.into_iter().map(|arg| (None, arg)).collect(),
+    }
+}
+
+/// A call to a function of a package, like `pkg::fun(a, b)`.
+pub fn ns_call(pkg: &str, name: &str, args: impl IntoIterator<Item = Expr>) -> Expr {
+    call(call(sym("::"), [sym(pkg), sym(name)]), args)
+}
+
+/// A binary operator call, like `lhs + rhs`.
+pub fn binary(op: &str, lhs: impl Into<Expr>, rhs: impl Into<Expr>) -> Expr {
+    call(sym(op), [lhs.into(), rhs.into()])
+}
+
+/// A unary operator call, like `-x`.
+pub fn unary(op: &str, operand: impl Into<Expr>) -> Expr {
+    call(sym(op), [operand.into()])
+}
+
+impl Expr {
+    /// Add a named argument to a call, like `fun(..., name = value)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the expression is not a call.
+    pub fn named(mut self, name: &str, value: impl Into<Expr>) -> Self {
+        match &mut self {
+            Expr::Call { args, .. } => args.push((Some(name.to_string()), value.into())),
+            _ => panic!("named arguments can only be added to calls"),
+        }
+        self
+    }
+
+    /// Add a positional argument to a call.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the expression is not a call.
+    pub fn arg(mut self, value: impl Into<Expr>) -> Self {
+        match &mut self {
+            Expr::Call { args, .. } => args.push((None, value.into())),
+            _ => panic!("arguments can only be added to calls"),
+        }
+        self
+    }
+
+    /// `self == rhs`
+    pub fn equal(self, rhs: impl Into<Expr>) -> Self {
+        binary("==", self, rhs)
+    }
+
+    /// `self != rhs`
+    pub fn not_equal(self, rhs: impl Into<Expr>) -> Self {
+        binary("!=", self, rhs)
+    }
+
+    /// `self < rhs`
+    pub fn less(self, rhs: impl Into<Expr>) -> Self {
+        binary("<", self, rhs)
+    }
+
+    /// `self <= rhs`
+    pub fn less_equal(self, rhs: impl Into<Expr>) -> Self {
+        binary("<=", self, rhs)
+    }
+
+    /// `self > rhs`
+    pub fn greater(self, rhs: impl Into<Expr>) -> Self {
+        binary(">", self, rhs)
+    }
+
+    /// `self >= rhs`
+    pub fn greater_equal(self, rhs: impl Into<Expr>) -> Self {
+        binary(">=", self, rhs)
+    }
+
+    /// `self$name`
+    pub fn dollar(self, name: &str) -> Self {
+        binary("$", self, sym(name))
+    }
+
+    /// `self[...]`
+    pub fn subset(self, index: impl IntoIterator<Item = Expr>) -> Self {
+        call(sym("["), std::iter::once(self).chain(index))
+    }
+
+    /// `self[[index]]`
+    pub fn subset2(self, index: impl Into<Expr>) -> Self {
+        binary("[[", self, index)
+    }
+
+    /// Convert the expression into an R object, a [`Lang`] for calls.
+    ///
+    /// The returned object is not protected.
+    pub fn to_sexp(&self) -> SEXP {
+        match self {
+            Expr::Symbol(name) => Symbol::new(name).get_sexp(),
+            Expr::Real(value) => value.into_sexp(),
+            Expr::Integer(value) => value.into_sexp(),
+            Expr::Logical(value) => value.into_sexp(),
+            Expr::String(value) => value.as_str().into_sexp(),
+            Expr::Null => r_nil(),
+            Expr::Missing => unsafe { R_MissingArg },
+            Expr::Value(sexp) => *sexp,
+            Expr::Call { function, args } => unsafe {
+                // Children are reachable from the protected call as soon as they are set.
+                let lang = Rf_protect(Rf_allocLang((args.len() + 1) as _));
+                SETCAR(lang, function.to_sexp());
+                let mut ptr = CDR(lang);
+                for (name, arg) in args {
+                    SETCAR(ptr, arg.to_sexp());
+                    if let Some(name) = name {
+                        SET_TAG(ptr, Symbol::new(name).get_sexp());
+                    }
+                    ptr = CDR(ptr);
+                }
+                Rf_unprotect(1);
+                lang
+            },
+        }
+    }
+
+    /// Convert the expression into a [`Lang`] ready for evaluation.
+    ///
+    /// Returns [`None`] if the expression is not a call.
+    pub fn build_lang(&self) -> Option<Lang<SEXP>> {
+        match self {
+            Expr::Call { .. } => Some(unsafe { Lang::wrap_sexp_unchecked(self.to_sexp()) }),
+            _ => None,
+        }
+    }
+
+    /// Deparse the expression into R code, like `deparse(quote(...))`.
+    pub fn deparse(&self) -> Result<String, CallError> {
+        let sexp: Protected<SEXP> = self.to_sexp().protect();
+        Ok(sexp
+            .deparse(DEPARSE_DEFAULT_WIDTH, DEPARSE_DEFAULT_OPTIONS)?
+            .join("\n"))
+    }
+}
+
+impl From<f64> for Expr {
+    fn from(value: f64) -> Self {
+        Expr::Real(value)
+    }
+}
+
+impl From<i32> for Expr {
+    fn from(value: i32) -> Self {
+        Expr::Integer(value)
+    }
+}
+
+impl From<bool> for Expr {
+    fn from(value: bool) -> Self {
+        Expr::Logical(value)
+    }
+}
+
+impl From<&str> for Expr {
+    fn from(value: &str) -> Self {
+        Expr::String(value.to_string())
+    }
+}
+
+impl From<String> for Expr {
+    fn from(value: String) -> Self {
+        Expr::String(value)
+    }
+}
+
+macro_rules! impl_binary_op {
+    ($trait:ident, $method:ident, $op:literal) => {
+        impl<R: Into<Expr>> $trait<R> for Expr {
+            type Output = Expr;
+
+            fn $method(self, rhs: R) -> Expr {
+                binary($op, self, rhs)
+            }
+        }
+    };
+}
+
+impl_binary_op!(Add, add, "+");
+impl_binary_op!(Sub, sub, "-");
+impl_binary_op!(Mul, mul, "*");
+impl_binary_op!(Div, div, "/");
+impl_binary_op!(BitAnd, bitand, "&");
+impl_binary_op!(BitOr, bitor, "|");
+
+impl Neg for Expr {
+    type Output = Expr;
+
+    fn neg(self) -> Expr {
+        unary("-", self)
+    }
+}
+
+impl Not for Expr {
+    type Output = Expr;
+
+    fn not(self) -> Expr {
+        unary("!", self)
+    }
+}
@@ -0,0 +1,317 @@
+use std::{
+    ffi::CString,
+    fmt::{Debug, Display},
+};
+
+use crate::{prelude::*, DowncastSEXP, DEPARSE_DEFAULT_OPTIONS, DEPARSE_DEFAULT_WIDTH};
+use derive_more::Deref;
+use libR_sys::*;
+
+use super::{
+    env::{Env, Symbol},
+    function::CallError,
+    lang::Lang,
+};
+
+#[derive(Deref)]
+#[repr(transparent)]
+/// A wrapper around a formula, a call to `~` with class `formula`, like `y ~ x + log(z)`.
+pub struct Formula<T: JustSEXP> {
+    lang: Lang<T>,
+}
+
+impl<T: JustSEXP> Debug for Formula<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match crate::deparse_for_debug(self) {
+            Some(code) => f
+                .debug_tuple("Formula")
+                .field(&format_args!("{}", code))
+                .finish(),
+            None => f
+                .debug_struct("Formula")
+                .field("sexp", &self.get_sexp())
+                .field("len", &unsafe { Rf_xlength(self.get_sexp()) })
+                .finish(),
+        }
+    }
+}
+
+impl<T: JustSEXP> HasSEXP for Formula<T> {
+    fn get_sexp(&self) -> SEXP {
+        self.lang.get_sexp()
+    }
+}
+
+impl<T: JustSEXP> Formula<T> {
+    /// The left-hand side, or [`None`] for one-sided formulas like `~ x`.
+    pub fn lhs(&self) -> Option<SEXP> {
+        unsafe {
+            if Rf_length(self.get_sexp()) == 3 {
+                Some(CADR(self.get_sexp()))
+            } else {
+                None
+            }
+        }
+    }
+
+    /// The right-hand side.
+    pub fn rhs(&self) -> SEXP {
+        unsafe {
+            if Rf_length(self.get_sexp()) == 3 {
+                CADDR(self.get_sexp())
+            } else {
+                CADR(self.get_sexp())
+            }
+        }
+    }
+
+    /// The environment the formula was created in, where its variables are looked up.
+    pub fn environment(&self) -> Option<Env<SEXP>> {
+        Env::wrap_sexp(self.attrib(Symbol::new(".Environment").get_sexp()))
+    }
+
+    /// The expanded terms of the right-hand side, like the `term.labels` of `terms(formula)`.
+    ///
+    /// `+` combines terms, `:` interacts them, `a * b` is `a + b + a:b`, `(a + b)^2` is all interactions up to order 2,
+    /// `- a` removes terms and `- 1` or `+ 0` removes the intercept. Anything else is a single variable.
+    ///
+    /// As in R, terms are ordered by their [`order`](Term::order), and by their first appearance within the same order.
+    /// Variables that are not symbols, like `log(z)`, are named by deparsing them, which can fail with an R error.
+    pub fn terms(&self) -> Result<Terms, CallError> {
+        let mut terms = Terms {
+            terms: Vec::new(),
+            intercept: true,
+        };
+        expand(self.rhs(), &mut terms)?;
+        // Stable, so terms of the same order keep their relative order.
+        terms.terms.sort_by_key(Term::order);
+        Ok(terms)
+    }
+
+    /// The names of all variables used in the formula, on both sides, like `all.vars(formula)`.
+    pub fn variables(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        unsafe {
+            collect_variables(CDR(self.get_sexp()), &mut names);
+        }
+        names
+    }
+}
+
+/// A single term of a [`Formula`], the interaction of one or more variables.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Term {
+    /// The deparsed variables of the term, like `x` and `log(z)`.
+    pub factors: Vec<String>,
+}
+
+impl Term {
+    /// The order of the term, `1` for main effects and `2` for two-way interactions.
+    pub fn order(&self) -> usize {
+        self.factors.len()
+    }
+
+    fn interact(&self, other: &Term) -> Term {
+        let mut factors = self.factors.clone();
+        for factor in &other.factors {
+            if !factors.contains(factor) {
+                factors.push(factor.clone());
+            }
+        }
+        Term { factors }
+    }
+
+    fn same_as(&self, other: &Term) -> bool {
+        self.order() == other.order() && self.factors.iter().all(|f| other.factors.contains(f))
+    }
+}
+
+impl Display for Term {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.factors.join(":"))
+    }
+}
+
+/// The expanded right-hand side of a [`Formula`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Terms {
+    /// The terms, main effects first, then interactions of increasing order.
+    pub terms: Vec<Term>,
+    /// Whether the model has an intercept.
+    pub intercept: bool,
+}
+
+impl Terms {
+    fn add(&mut self, term: Term) {
+        if !self.terms.iter().any(|t| t.same_as(&term)) {
+            self.terms.push(term);
+        }
+    }
+
+    fn remove(&mut self, term: &Term) {
+        self.terms.retain(|t| !t.same_as(term));
+    }
+}
+
+fn numeric_value(sexp: SEXP) -> Option<f64> {
+    match unsafe { TYPEOF(sexp) } {
+        SEXPTYPE::REALSXP | SEXPTYPE::INTSXP if unsafe { Rf_length(sexp) } == 1 => {
+            Some(unsafe { Rf_asReal(sexp) })
+        }
+        _ => None,
+    }
+}
+
+fn variable_name(sexp: SEXP) -> Result<String, CallError> {
+    match Symbol::<SEXP>::wrap_sexp(sexp) {
+        Some(symbol) => Ok(symbol.name()),
+        None => Ok(sexp
+            .deparse(DEPARSE_DEFAULT_WIDTH, DEPARSE_DEFAULT_OPTIONS)?
+            .join(" ")),
+    }
+}
+
+/// Expand an expression of the right-hand side into `terms`.
+fn expand(sexp: SEXP, terms: &mut Terms) -> Result<(), CallError> {
+    if let Some(value) = numeric_value(sexp) {
+        terms.intercept = value != 0.0;
+        return Ok(());
+    }
+    let Some(lang) = Lang::<SEXP>::wrap_sexp(sexp) else {
+        terms.add(Term {
+            factors: vec![variable_name(sexp)?],
+        });
+        return Ok(());
+    };
+    let (op, args) = unsafe {
+        let op = Symbol::<SEXP>::wrap_sexp(CAR(lang.get_sexp())).map(|op| op.name());
+        let mut args = Vec::new();
+        let mut ptr = CDR(lang.get_sexp());
+        while ptr != R_NilValue {
+            args.push(CAR(ptr));
+            ptr = CDR(ptr);
+        }
+        (op, args)
+    };
+    match (op.as_deref(), args.as_slice()) {
+        (Some("+"), [operand]) | (Some("("), [operand]) => expand(*operand, terms)?,
+        (Some("+"), [lhs, rhs]) => {
+            expand(*lhs, terms)?;
+            expand(*rhs, terms)?;
+        }
+        (Some("-"), [operand]) => subtract(*operand, terms)?,
+        (Some("-"), [lhs, rhs]) => {
+            expand(*lhs, terms)?;
+            subtract(*rhs, terms)?;
+        }
+        (Some(":"), [lhs, rhs]) => {
+            for term in interact(&expand_new(*lhs)?.terms, &expand_new(*rhs)?.terms) {
+                terms.add(term);
+            }
+        }
+        (Some("*"), [lhs, rhs]) => {
+            let lhs = expand_new(*lhs)?.terms;
+            let rhs = expand_new(*rhs)?.terms;
+            for term in lhs.iter().chain(rhs.iter()).cloned() {
+                terms.add(term);
+            }
+            for term in interact(&lhs, &rhs) {
+                terms.add(term);
+            }
+        }
+        (Some("^"), [base, power]) if numeric_value(*power).is_some() => {
+            let order = numeric_value(*power).unwrap_or(1.0) as usize;
+            let base = expand_new(*base)?.terms;
+            let mut result = base.clone();
+            for _ in 1..order {
+                for term in interact(&result, &base) {
+                    if term.order() <= order && !result.iter().any(|t| t.same_as(&term)) {
+                        result.push(term);
+                    }
+                }
+            }
+            for term in result {
+                terms.add(term);
+            }
+        }
+        _ => terms.add(Term {
+            factors: vec![variable_name(sexp)?],
+        }),
+    }
+    Ok(())
+}
+
+fn expand_new(sexp: SEXP) -> Result<Terms, CallError> {
+    let mut terms = Terms {
+        terms: Vec::new(),
+        intercept: true,
+    };
+    expand(sexp, &mut terms)?;
+    Ok(terms)
+}
+
+fn subtract(sexp: SEXP, terms: &mut Terms) -> Result<(), CallError> {
+    if numeric_value(sexp).is_some() {
+        terms.intercept = false;
+        return Ok(());
+    }
+    for term in expand_new(sexp)?.terms {
+        terms.remove(&term);
+    }
+    Ok(())
+}
+
+fn interact(lhs: &[Term], rhs: &[Term]) -> Vec<Term> {
+    lhs.iter()
+        .flat_map(|l| rhs.iter().map(move |r| l.interact(r)))
+        .collect()
+}
+
+/// Collect the symbols used as values in the arguments `ptr`, skipping function names.
+unsafe fn collect_variables(mut ptr: SEXP, names: &mut Vec<String>) {
+    while ptr != R_NilValue {
+        let value = CAR(ptr);
+        match TYPEOF(value) {
+            SEXPTYPE::SYMSXP if value != R_MissingArg => {
+                let name = Symbol::<SEXP>::wrap_sexp_unchecked(value).name();
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+            SEXPTYPE::LANGSXP => collect_variables(CDR(value), names),
+            _ => {}
+        }
+        ptr = CDR(ptr);
+    }
+}
+
+unsafe impl<T: JustSEXP> JustSEXP for Formula<T> {
+    type Inner = Lang<T>;
+    fn inner_ref(&self) -> &Self::Inner {
+        &self.lang
+    }
+    unsafe fn wrap_sexp_unchecked(sexp: SEXP) -> Self {
+        Formula {
+            lang: Lang::wrap_sexp_unchecked(sexp),
+        }
+    }
+    fn wrap_sexp(sexp: SEXP) -> Option<Self> {
+        let formula = CString::new("formula").unwrap();
+        if unsafe { Rf_inherits(sexp, formula.as_ptr()) }.into() {
+            Some(Formula {
+                lang: Lang::wrap_sexp(sexp)?,
+            })
+        } else {
+            None
+        }
+    }
+    fn upcast(self) -> Self::Inner {
+        self.lang
+    }
+}
+
+impl<T: JustSEXP> DowncastSEXP<Formula<T>> for T {
+    fn downcast(self) -> Option<Formula<T>> {
+        Formula::wrap_sexp(self.get_sexp())
+    }
+}
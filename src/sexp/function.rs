@@ -1,8 +1,18 @@
-use std::fmt::Debug;
+use std::fmt::{Debug, Display};
 
-use crate::{prelude::*, DowncastSEXP, ProtectedSEXP};
+use crate::{
+    convert::{CallArgs, FromSexp, IntoArgs},
+    message::geterrmessage,
+    prelude::*,
+    AnySexp, DowncastSEXP, ProtectedSEXP,
+};
 use derive_more::Deref;
-use libR_sys::Rf_isFunction;
+use libR_sys::*;
+
+use super::{
+    env::{Env, Symbol},
+    lang::Pairlist,
+};
 
 #[derive(Deref)]
 #[repr(transparent)]
@@ -126,11 +136,120 @@ impl<T: JustSEXP> Closure<T> {
     pub fn new(sexp: T) -> Self {
         Closure { sexp }
     }
+
+    /// Create a new closure object if `sexp` is an R closure (`CLOSXP`).
+    ///
+    /// Unlike downcasting, which accepts any function, this rejects builtins and specials,
+    /// so the formals, body and environment accessors can be used.
+    pub fn try_new(sexp: T) -> Option<Self> {
+        if sexp.sexp_type() == SEXPTYPE::CLOSXP {
+            Some(Closure { sexp })
+        } else {
+            None
+        }
+    }
+
+    /// Check if this is an R closure (`CLOSXP`) rather than a builtin or special.
+    pub fn is_closure(&self) -> bool {
+        self.sexp_type() == SEXPTYPE::CLOSXP
+    }
+
+    #[track_caller]
+    fn assert_closure(&self) {
+        assert!(
+            self.is_closure(),
+            "expected a closure, got {:?}",
+            self.sexp_type()
+        );
+    }
+
+    /// The formal arguments of the closure, like `formals(f)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is a builtin or special, see [`Closure::try_new`].
+    pub fn formals(&self) -> Pairlist<SEXP> {
+        self.assert_closure();
+        unsafe { Pairlist::wrap_sexp_unchecked(FORMALS(self.get_sexp())) }
+    }
+
+    /// The body of the closure, like `body(f)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is a builtin or special, see [`Closure::try_new`].
+    pub fn body(&self) -> AnySexp<SEXP> {
+        self.assert_closure();
+        AnySexp::from(unsafe { BODY(self.get_sexp()) })
+    }
+
+    /// The enclosing environment of the closure, like `environment(f)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is a builtin or special, see [`Closure::try_new`].
+    pub fn environment(&self) -> Env<SEXP> {
+        self.assert_closure();
+        unsafe { Env::wrap_sexp_unchecked(CLOENV(self.get_sexp())) }
+    }
+
+    /// Replace the formal arguments of the closure, like `formals(f) <- value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is a builtin or special, see [`Closure::try_new`].
+    pub fn set_formals<F: JustSEXP>(&mut self, formals: &Pairlist<F>) {
+        self.assert_closure();
+        unsafe { SET_FORMALS(self.get_sexp(), formals.get_sexp()) }
+    }
+
+    /// Replace the body of the closure, like `body(f) <- value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is a builtin or special, see [`Closure::try_new`].
+    pub fn set_body(&mut self, body: impl HasSEXP) {
+        self.assert_closure();
+        unsafe { SET_BODY(self.get_sexp(), body.get_sexp()) }
+    }
+
+    /// Replace the enclosing environment of the closure, like `environment(f) <- value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is a builtin or special, see [`Closure::try_new`].
+    pub fn set_environment<E: JustSEXP>(&mut self, env: &Env<E>) {
+        self.assert_closure();
+        unsafe { SET_CLOENV(self.get_sexp(), env.get_sexp()) }
+    }
 }
 
-impl<T: JustSEXP + Debug> Debug for Closure<T> {
+impl Closure<SEXP> {
+    /// Create a closure from its formal arguments, body and enclosing environment, like `as.function(c(formals, body), env)`.
+    ///
+    /// The body is usually a [`Lang`](super::lang::Lang), a symbol or a constant.
+    pub fn from_parts<F: JustSEXP, E: JustSEXP>(
+        formals: &Pairlist<F>,
+        body: impl HasSEXP,
+        env: &Env<E>,
+    ) -> Self {
+        unsafe {
+            Closure {
+                sexp: Rf_mkCLOSXP(formals.get_sexp(), body.get_sexp(), env.get_sexp()),
+            }
+        }
+    }
+}
+
+impl<T: JustSEXP> Debug for Closure<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_tuple("Closure").field(&self.sexp).finish()
+        match crate::deparse_for_debug(self) {
+            Some(code) => f
+                .debug_tuple("Closure")
+                .field(&format_args!("{}", code))
+                .finish(),
+            None => f.debug_tuple("Closure").field(&self.get_sexp()).finish(),
+        }
     }
 }
 
@@ -183,6 +302,122 @@ impl<T: JustSEXP> HasSEXP for Callable<T> {
     }
 }
 
+impl<T: JustSEXP> Callable<T> {
+    /// Call the function with the given arguments, evaluating the call in `env`.
+    ///
+    /// `args` is a tuple of values implementing [`IntoSexp`](crate::convert::IntoSexp),
+    /// wrap a value with [`named`](crate::convert::named) to pass it by name.
+    /// The arguments are converted and protected until the call returns, the result is not protected.
+    ///
+    /// For closures, the arguments are checked against the formals before the call is made.
+    pub fn call<R: FromSexp, E: JustSEXP>(
+        &self,
+        env: &Env<E>,
+        args: impl IntoArgs,
+    ) -> Result<R, CallError> {
+        let mut call_args = CallArgs::new();
+        args.push_args(&mut call_args);
+        let args = call_args.as_slice();
+        self.check_arity(args)?;
+        let result = unsafe {
+            let lang = Rf_protect(Rf_allocLang((args.len() + 1) as _));
+            SETCAR(lang, self.get_sexp());
+            let mut ptr = CDR(lang);
+            for (tag, value) in args {
+                SETCAR(ptr, *value);
+                if let Some(tag) = tag {
+                    SET_TAG(ptr, tag.get_sexp());
+                }
+                ptr = CDR(ptr);
+            }
+            let checked = crate::debug::protect_stack_len();
+            let mut error = 0;
+            let result = R_tryEval(lang, env.get_sexp(), &mut error);
+            Rf_unprotect(1);
+            if error != 0 {
+                crate::debug::protect_stack_truncate(checked);
+                return Err(CallError::Eval(geterrmessage()));
+            }
+            result
+        };
+        R::from_sexp(result).ok_or(CallError::Conversion(std::any::type_name::<R>()))
+    }
+
+    fn check_arity(&self, args: &[(Option<Symbol<SEXP>>, SEXP)]) -> Result<(), CallError> {
+        let Some(closure) = Closure::try_new(self.get_sexp()) else {
+            return Ok(());
+        };
+        let formals = closure
+            .formals()
+            .iter()
+            .filter_map(|(tag, _)| tag)
+            .collect::<Vec<_>>();
+        if formals
+            .iter()
+            .any(|formal| formal.get_sexp() == unsafe { R_DotsSymbol })
+        {
+            return Ok(());
+        }
+        if args.len() > formals.len() {
+            return Err(CallError::TooManyArguments {
+                expected: formals.len(),
+                given: args.len(),
+            });
+        }
+        for name in args.iter().filter_map(|(tag, _)| tag.as_ref()) {
+            let name = name.name();
+            // Arguments can be given by a prefix of the formal name.
+            if !formals
+                .iter()
+                .any(|formal| formal.name().starts_with(&name))
+            {
+                return Err(CallError::UnusedArgument(name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Errors from calling a function with [`Callable::call`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallError {
+    /// The function or object could not be found, with what was missing if available.
+    NotFound(Option<String>),
+    /// More arguments were given than the closure accepts.
+    TooManyArguments {
+        /// The number of formal arguments.
+        expected: usize,
+        /// The number of arguments given.
+        given: usize,
+    },
+    /// A named argument does not match any formal argument.
+    UnusedArgument(String),
+    /// The call raised an R error, with its message if available.
+    Eval(Option<String>),
+    /// The result could not be converted to the requested type.
+    Conversion(&'static str),
+}
+
+impl Display for CallError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallError::NotFound(Some(message)) => write!(f, "not found: {}", message),
+            CallError::NotFound(None) => write!(f, "not found"),
+            CallError::TooManyArguments { expected, given } => write!(
+                f,
+                "unused arguments: {} given, the function accepts {}",
+                given, expected
+            ),
+            CallError::UnusedArgument(name) => write!(f, "unused argument: {}", name),
+            CallError::Eval(Some(message)) => write!(f, "{}", message.trim_end()),
+            CallError::Eval(None) => write!(f, "error in call"),
+            CallError::Conversion(ty) => write!(f, "cannot convert the result to {}", ty),
+        }
+    }
+}
+
+impl std::error::Error for CallError {}
+
 impl<T: JustSEXP> DowncastSEXP<Callable<T>> for T {
     fn downcast(self) -> Option<Callable<T>> {
         Callable::wrap_sexp(self.get_sexp())
@@ -1,10 +1,10 @@
-use std::fmt::Debug;
+use std::{any::Any, fmt::Debug};
 
 use crate::{message::geterrmessage, prelude::*, DowncastSEXP, ProtectedSEXP};
 use derive_more::Deref;
 use libR_sys::*;
 
-use super::env::Env;
+use super::env::{Env, Symbol};
 
 #[derive(Deref)]
 #[repr(transparent)]
@@ -13,9 +13,19 @@ pub struct Lang<T: JustSEXP> {
     head: T,
 }
 
-impl<T: JustSEXP + Debug> Debug for Lang<T> {
+impl<T: JustSEXP> Debug for Lang<T> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_tuple("Lang").field(&self.head).finish()
+        match crate::deparse_for_debug(self) {
+            Some(code) => f
+                .debug_tuple("Lang")
+                .field(&format_args!("{}", code))
+                .finish(),
+            None => f
+                .debug_struct("Lang")
+                .field("sexp", &self.get_sexp())
+                .field("len", &unsafe { Rf_xlength(self.get_sexp()) })
+                .finish(),
+        }
     }
 }
 
@@ -29,9 +39,11 @@ impl<T: JustSEXP> Lang<T> {
     /// Evaluate the language object in the given environment.
     pub fn eval<E: JustSEXP>(self, env: Env<E>) -> Option<SEXP> {
         unsafe {
+            let checked = crate::debug::protect_stack_len();
             let mut error = 0;
             let result = R_tryEval(self.get_sexp(), env.get_sexp(), &mut error);
             if error != 0 {
+                crate::debug::protect_stack_truncate(checked);
                 None
             } else {
                 Some(result)
@@ -81,10 +93,32 @@ impl<T: JustSEXP> TypedSEXP for Lang<T> {
     const SEXP_TYPE: SEXPTYPE = SEXPTYPE::LANGSXP;
 }
 
+/// A tag of a pairlist cell, either a [`Symbol`] or a name.
+pub trait IntoTag {
+    /// Convert into the symbol used as the tag.
+    fn into_tag(self) -> Symbol<SEXP>;
+}
+
+impl<S: JustSEXP> IntoTag for Symbol<S> {
+    fn into_tag(self) -> Symbol<SEXP> {
+        unsafe { Symbol::wrap_sexp_unchecked(self.get_sexp()) }
+    }
+}
+
+impl IntoTag for &str {
+    fn into_tag(self) -> Symbol<SEXP> {
+        Symbol::new(self)
+    }
+}
+
 /// A builder for creating a pairlist.
+///
+/// Pushed values stay protected until the builder is dropped, and are released in reverse order.
 pub struct PairlistBuilder<T: ProtectedSEXP> {
     head: T,
-    cdr: Vec<(SEXP, Option<SEXP>)>,
+    cdr: Vec<(SEXP, Option<Symbol<SEXP>>)>,
+    /// The pushed values, kept alive until the builder is dropped.
+    guards: Vec<Box<dyn Any>>,
 }
 
 impl<T: ProtectedSEXP> PairlistBuilder<T>
@@ -96,42 +130,213 @@ where
         PairlistBuilder {
             head,
             cdr: Vec::new(),
+            guards: Vec::new(),
         }
     }
 
     /// Push a pair onto the pairlist.
-    pub fn push<U: HasSEXP + ProtectedSEXP>(mut self, sexp: U) -> Self {
+    pub fn push<U: HasSEXP + ProtectedSEXP + 'static>(mut self, sexp: U) -> Self {
         self.cdr.push((sexp.get_sexp(), None));
+        self.guards.push(Box::new(sexp));
         self
     }
 
-    /// Push a tagged pair onto the pairlist.
-    pub fn push_tagged<U: HasSEXP + ProtectedSEXP, V: HasSEXP + ProtectedSEXP>(
+    /// Push a pair tagged with a name onto the pairlist, like `name = value` in a call.
+    pub fn push_tagged<U: HasSEXP + ProtectedSEXP + 'static>(
         mut self,
-        tag: U,
-        sexp: V,
+        tag: impl IntoTag,
+        sexp: U,
     ) -> Self {
-        self.cdr.push((tag.get_sexp(), Some(sexp.get_sexp())));
+        self.cdr.push((sexp.get_sexp(), Some(tag.into_tag())));
+        self.guards.push(Box::new(sexp));
         self
     }
 
     /// Build the pairlist into a [`Lang`].
     pub fn build_lang(self) -> Lang<<T as ProtectedSEXP>::Inner> {
         unsafe {
-            let lang = Rf_allocLang((self.cdr.len() + 1) as _);
-            Rf_protect(lang);
-            let mut ptr = lang;
+            let lang = Rf_allocLang((self.cdr.len() + 1) as _).protect();
+            let mut ptr = lang.get_sexp();
             SETCAR(ptr, self.head.get_sexp());
             ptr = CDR(ptr);
-            for arg in self.cdr {
-                SETCAR(ptr, arg.0);
-                if let Some(tag) = arg.1 {
-                    SET_TAG(ptr, tag);
+            for (value, tag) in &self.cdr {
+                SETCAR(ptr, *value);
+                if let Some(tag) = tag {
+                    SET_TAG(ptr, tag.get_sexp());
                 }
                 ptr = CDR(ptr);
             }
-            Rf_unprotect(1);
-            Lang::wrap_sexp_unchecked(lang)
+            Lang::wrap_sexp_unchecked(lang.unprotect())
+        }
+    }
+
+    /// Build the pairlist into a [`Lang`], rejecting tags that are used more than once.
+    pub fn try_build_lang(self) -> Result<Lang<<T as ProtectedSEXP>::Inner>, DuplicateTag> {
+        let mut tags = self
+            .cdr
+            .iter()
+            .filter_map(|(_, tag)| tag.as_ref())
+            .collect::<Vec<_>>();
+        tags.sort_by_key(|tag| tag.get_sexp());
+        if let Some(duplicate) = tags
+            .windows(2)
+            .find(|pair| pair[0].get_sexp() == pair[1].get_sexp())
+        {
+            return Err(DuplicateTag(duplicate[0].name()));
+        }
+        Ok(self.build_lang())
+    }
+}
+
+impl<T: ProtectedSEXP> Drop for PairlistBuilder<T> {
+    fn drop(&mut self) {
+        while let Some(guard) = self.guards.pop() {
+            drop(guard);
+        }
+    }
+}
+
+/// A tag that was used more than once in a [`PairlistBuilder`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DuplicateTag(pub String);
+
+impl std::fmt::Display for DuplicateTag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "formal argument \"{}\" matched by multiple actual arguments",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for DuplicateTag {}
+
+#[derive(Deref)]
+#[repr(transparent)]
+/// A wrapper around a pairlist, such as the formal arguments of a closure.
+///
+/// The empty pairlist is `NULL`.
+pub struct Pairlist<T: JustSEXP> {
+    sexp: T,
+}
+
+impl<T: JustSEXP + Debug> Debug for Pairlist<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Pairlist").field(&self.sexp).finish()
+    }
+}
+
+impl<T: JustSEXP> HasSEXP for Pairlist<T> {
+    fn get_sexp(&self) -> SEXP {
+        self.sexp.get_sexp()
+    }
+}
+
+impl Pairlist<SEXP> {
+    /// Create a pairlist from tagged values.
+    ///
+    /// The values are not protected by the pairlist until it is returned, they must be protected by the caller.
+    pub fn from_tagged<S: JustSEXP>(items: impl IntoIterator<Item = (Symbol<S>, SEXP)>) -> Self {
+        let items = items.into_iter().collect::<Vec<_>>();
+        unsafe {
+            let list = Rf_allocList(items.len() as _);
+            let mut ptr = list;
+            for (tag, value) in items {
+                SET_TAG(ptr, tag.get_sexp());
+                SETCAR(ptr, value);
+                ptr = CDR(ptr);
+            }
+            Pairlist { sexp: list }
         }
     }
+
+    /// Create the formal arguments of a closure, like `alist(x = , y = 1)`.
+    ///
+    /// Arguments without a default value are given as [`None`].
+    pub fn formals(args: &[(&str, Option<SEXP>)]) -> Self {
+        Pairlist::from_tagged(args.iter().map(|(name, default)| {
+            (
+                Symbol::new(name),
+                default.unwrap_or(unsafe { R_MissingArg }),
+            )
+        }))
+    }
+}
+
+impl<T: JustSEXP> Pairlist<T> {
+    /// The number of elements in the pairlist.
+    pub fn len(&self) -> usize {
+        unsafe { Rf_length(self.get_sexp()) as usize }
+    }
+
+    /// Check if the pairlist is empty.
+    pub fn is_empty(&self) -> bool {
+        self.get_sexp() == unsafe { R_NilValue }
+    }
+
+    /// Iterate over the tags and values of the pairlist.
+    ///
+    /// Untagged elements yield [`None`] as the tag, formals without a default value yield `R_MissingArg`.
+    pub fn iter(&self) -> PairlistIter<'_> {
+        PairlistIter {
+            ptr: self.get_sexp(),
+            _marker: std::marker::PhantomData,
+        }
+    }
+}
+
+/// An iterator over the tags and values of a [`Pairlist`].
+pub struct PairlistIter<'a> {
+    ptr: SEXP,
+    _marker: std::marker::PhantomData<&'a ()>,
+}
+
+impl Iterator for PairlistIter<'_> {
+    type Item = (Option<Symbol<SEXP>>, SEXP);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        unsafe {
+            if self.ptr == R_NilValue {
+                return None;
+            }
+            let tag = Symbol::wrap_sexp(TAG(self.ptr));
+            let value = CAR(self.ptr);
+            self.ptr = CDR(self.ptr);
+            Some((tag, value))
+        }
+    }
+}
+
+unsafe impl<T: JustSEXP> JustSEXP for Pairlist<T> {
+    type Inner = T;
+    fn inner_ref(&self) -> &Self::Inner {
+        &self.sexp
+    }
+    unsafe fn wrap_sexp_unchecked(sexp: SEXP) -> Self {
+        Pairlist {
+            sexp: T::wrap_sexp_unchecked(sexp),
+        }
+    }
+    fn wrap_sexp(sexp: SEXP) -> Option<Self> {
+        if matches!(
+            unsafe { TYPEOF(sexp) },
+            SEXPTYPE::LISTSXP | SEXPTYPE::NILSXP
+        ) {
+            Some(Pairlist {
+                sexp: T::wrap_sexp(sexp)?,
+            })
+        } else {
+            None
+        }
+    }
+    fn upcast(self) -> Self::Inner {
+        self.sexp
+    }
+}
+
+impl<T: JustSEXP> DowncastSEXP<Pairlist<T>> for T {
+    fn downcast(self) -> Option<Pairlist<T>> {
+        Pairlist::wrap_sexp(self.get_sexp())
+    }
 }
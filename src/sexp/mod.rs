@@ -1,19 +1,46 @@
 //! This module contains typed wrappers for R's SEXP type.
 
+/// A wrapper around R arrays.
+pub mod array;
+
 /// A wrapper around R matrices.
 pub mod matrix;
 
 /// Wrappers around simple R vectors.
 pub mod vector;
 
+/// A wrapper around sparse matrices from the Matrix package.
+pub mod sparse;
+
 /// A wrapper around R's language objects.
 pub mod lang;
 
+/// A quasi-quotation builder for R's language objects.
+pub mod expr;
+
+/// Visitors over R's syntax trees.
+pub mod visit;
+
+/// A wrapper around R's formulas.
+pub mod formula;
+
 /// A wrapper around R's environments.
 pub mod env;
 
+/// A wrapper around R6 objects.
+pub mod r6;
+
+/// Package namespace and exported function lookup.
+pub mod namespace;
+
+/// A wrapper around R's promises.
+pub mod promise;
+
 /// A wrapper around R's functions.
 pub mod function;
 
+/// A wrapper around S4 objects.
+pub mod s4;
+
 /// A wrapper around R's external pointers.
 pub mod ptr;
@@ -0,0 +1,165 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    ffi::c_void,
+};
+
+use crate::{
+    convert::{FromSexp, IntoArgs},
+    message::geterrmessage,
+    prelude::*,
+    protect::BoxProtected,
+};
+use libR_sys::*;
+
+use super::{
+    env::{Env, Symbol},
+    function::{CallError, Callable},
+    promise::force_if_promise,
+};
+
+impl Env<SEXP> {
+    /// Get the namespace of a package, loading it if needed, like `getNamespace(name)`.
+    ///
+    /// Loading errors (e.g. the package is not installed) are returned as [`CallError::Eval`] instead of raised.
+    pub fn namespace(name: &str) -> Result<Self, CallError> {
+        unsafe extern "C" fn find_namespace(data: *mut c_void) {
+            let data = &mut *(data as *mut (SEXP, SEXP));
+            data.1 = R_FindNamespace(data.0);
+        }
+
+        let info = CharacterVectorSEXP::scalar(name).protect();
+        let mut data = (info.get_sexp(), r_nil());
+        let checked = crate::debug::protect_stack_len();
+        let success: bool = unsafe {
+            R_ToplevelExec(
+                Some(find_namespace),
+                &mut data as *mut (SEXP, SEXP) as *mut c_void,
+            )
+        }
+        .into();
+        if !success {
+            crate::debug::protect_stack_truncate(checked);
+            return Err(CallError::Eval(geterrmessage()));
+        }
+        Env::wrap_sexp(data.1)
+            .ok_or_else(|| CallError::NotFound(Some(format!("namespace '{}'", name))))
+    }
+
+    /// The names exported by a package, like `getNamespaceExports(name)`.
+    pub fn package_exports(name: &str) -> Result<CharacterVectorSEXP<SEXP>, CallError> {
+        let ns = Env::namespace(name)?;
+        if ns.get_sexp() == unsafe { R_BaseNamespace } {
+            return Ok(ns.names(true, true));
+        }
+        Ok(exports_env(&ns)?.names(true, true))
+    }
+}
+
+/// An environment in the `.__NAMESPACE__.` info of a namespace, like `getNamespaceInfo(ns, which)`.
+fn namespace_info(ns: &Env<SEXP>, which: &str) -> Option<Env<SEXP>> {
+    ns.peek(Symbol::new(".__NAMESPACE__."))
+        .and_then(|info| info.downcast_to::<Env<_>>())
+        .and_then(|info| info.peek(Symbol::new(which)))
+        .and_then(|env| env.downcast_to::<Env<_>>())
+}
+
+fn exports_env(ns: &Env<SEXP>) -> Result<Env<SEXP>, CallError> {
+    namespace_info(ns, "exports")
+        .ok_or_else(|| CallError::NotFound(Some("namespace has no export table".to_string())))
+}
+
+/// Force a looked up value, reporting R errors raised while forcing it.
+fn forced(value: SEXP) -> Result<SEXP, CallError> {
+    force_if_promise(value).ok_or_else(|| CallError::Eval(geterrmessage()))
+}
+
+/// Get an exported object of a package, like `getExportedValue(pkg, name)` or `pkg::name`.
+///
+/// Exported names are mapped to their internal names through the export table and looked up from the namespace,
+/// so objects the package re-exports from its imports (like dplyr's `%>%`) are found. Lazy-loaded datasets are also found.
+/// Lazy-loaded objects are forced.
+///
+/// Missing objects are reported as [`CallError::NotFound`], and errors from loading the namespace or forcing the object as [`CallError::Eval`].
+pub fn get_exported_value(pkg: &str, name: &str) -> Result<SEXP, CallError> {
+    let ns = Env::namespace(pkg)?;
+    if ns.get_sexp() == unsafe { R_BaseNamespace } {
+        let value = ns
+            .peek(Symbol::new(name))
+            .ok_or_else(|| CallError::NotFound(Some(format!("object '{}'", name))))?;
+        return forced(value);
+    }
+
+    if let Some(internal) = exports_env(&ns)?.peek(Symbol::new(name)) {
+        // The export table maps exported names to internal names, which usually but not always match.
+        let internal = internal
+            .downcast_to::<CharacterVectorSEXP<_>>()
+            .filter(|internal| internal.len() == 1)
+            .map(|internal| internal.get_elt(0).to_string())
+            .unwrap_or_else(|| name.to_string());
+        // Like `get(internal, envir = ns)`, the namespace's parents hold its imports.
+        let value = ns
+            .get_inherited(Symbol::new(&internal))
+            .ok_or_else(|| CallError::NotFound(Some(format!("object '{}'", internal))))?;
+        return forced(value);
+    }
+
+    if let Some(value) =
+        namespace_info(&ns, "lazydata").and_then(|data| data.peek(Symbol::new(name)))
+    {
+        return forced(value);
+    }
+
+    Err(CallError::NotFound(Some(format!(
+        "'{}' is not an exported object from 'namespace:{}'",
+        name, pkg
+    ))))
+}
+
+/// Get an exported function of a package, like `pkg::name`, see [`get_exported_value`].
+pub fn get_function(pkg: &str, name: &str) -> Result<Callable<SEXP>, CallError> {
+    get_exported_value(pkg, name)?
+        .downcast_to::<Callable<_>>()
+        .ok_or_else(|| CallError::NotFound(Some(format!("'{}::{}' is not a function", pkg, name))))
+}
+
+/// Call an exported function of a package, like `pkg::name(...)`.
+///
+/// The call is evaluated in the global environment, see [`Callable::call`].
+pub fn call_in_namespace<R: FromSexp>(
+    pkg: &str,
+    name: &str,
+    args: impl IntoArgs,
+) -> Result<R, CallError> {
+    get_function(pkg, name)?.call(&Env::global(), args)
+}
+
+/// A cache of functions resolved with [`get_function`].
+///
+/// Resolved functions are kept protected for as long as the cache is alive.
+#[derive(Default)]
+pub struct FunctionCache {
+    functions: HashMap<(String, String), BoxProtected<Callable<SEXP>>>,
+}
+
+impl FunctionCache {
+    /// Create an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get an exported function of a package, resolving it on first use.
+    ///
+    /// The returned callable is protected by the cache, and borrows it so it can't outlive [`FunctionCache::clear`].
+    pub fn get(&mut self, pkg: &str, name: &str) -> Result<&Callable<SEXP>, CallError> {
+        let function = match self.functions.entry((pkg.to_string(), name.to_string())) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(get_function(pkg, name)?.protect_box()),
+        };
+        Ok(&**function)
+    }
+
+    /// Drop all cached functions.
+    pub fn clear(&mut self) {
+        self.functions.clear();
+    }
+}
@@ -0,0 +1,114 @@
+use std::fmt::Debug;
+
+use crate::{prelude::*, DowncastSEXP};
+use derive_more::Deref;
+use libR_sys::*;
+
+use super::env::Env;
+
+#[derive(Deref)]
+#[repr(transparent)]
+/// A wrapper around a promise, the lazily evaluated value of a function argument, `delayedAssign` or lazy-loaded data.
+pub struct Promise<T: JustSEXP> {
+    sexp: T,
+}
+
+impl<T: JustSEXP + Debug> Debug for Promise<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Promise").field(&self.sexp).finish()
+    }
+}
+
+impl<T: JustSEXP> HasSEXP for Promise<T> {
+    fn get_sexp(&self) -> SEXP {
+        self.sexp.get_sexp()
+    }
+}
+
+impl<T: JustSEXP> Promise<T> {
+    /// The unevaluated expression of the promise.
+    pub fn expression(&self) -> SEXP {
+        unsafe { PRCODE(self.get_sexp()) }
+    }
+
+    /// The environment the promise will be evaluated in, or [`None`] once it has been forced.
+    pub fn environment(&self) -> Option<Env<SEXP>> {
+        Env::wrap_sexp(unsafe { PRENV(self.get_sexp()) })
+    }
+
+    /// Check if the promise has already been forced.
+    pub fn is_forced(&self) -> bool {
+        unsafe { PRVALUE(self.get_sexp()) != R_UnboundValue }
+    }
+
+    /// The value of the promise, if it has already been forced.
+    pub fn value(&self) -> Option<SEXP> {
+        if self.is_forced() {
+            Some(unsafe { PRVALUE(self.get_sexp()) })
+        } else {
+            None
+        }
+    }
+
+    /// Force the promise, evaluating the expression in its environment once and caching the value.
+    ///
+    /// Returns [`None`] if the evaluation fails, the promise stays unforced in that case.
+    pub fn force(&self) -> Option<SEXP> {
+        if let Some(value) = self.value() {
+            return Some(value);
+        }
+        unsafe {
+            let checked = crate::debug::protect_stack_len();
+            let mut error = 0;
+            let result = R_tryEval(self.get_sexp(), R_BaseEnv, &mut error);
+            if error != 0 {
+                crate::debug::protect_stack_truncate(checked);
+                None
+            } else {
+                Some(result)
+            }
+        }
+    }
+}
+
+/// Force the value if it is a promise, otherwise return it as is.
+pub(crate) fn force_if_promise(value: SEXP) -> Option<SEXP> {
+    match Promise::<SEXP>::wrap_sexp(value) {
+        Some(promise) => promise.force(),
+        None => Some(value),
+    }
+}
+
+unsafe impl<T: JustSEXP> JustSEXP for Promise<T> {
+    type Inner = T;
+    fn inner_ref(&self) -> &Self::Inner {
+        &self.sexp
+    }
+    unsafe fn wrap_sexp_unchecked(sexp: SEXP) -> Self {
+        Promise {
+            sexp: T::wrap_sexp_unchecked(sexp),
+        }
+    }
+    fn wrap_sexp(sexp: SEXP) -> Option<Self> {
+        if unsafe { TYPEOF(sexp) } == SEXPTYPE::PROMSXP {
+            Some(Promise {
+                sexp: T::wrap_sexp(sexp)?,
+            })
+        } else {
+            None
+        }
+    }
+    fn upcast(self) -> Self::Inner {
+        self.sexp
+    }
+}
+
+impl<T: JustSEXP> TypedSEXP for Promise<T> {
+    const SEXP_TYPE: SEXPTYPE = SEXPTYPE::PROMSXP;
+}
+
+impl<T: JustSEXP> DowncastSEXP<Promise<T>> for T {
+    fn downcast(self) -> Option<Promise<T>> {
+        Promise::wrap_sexp(self.get_sexp())
+    }
+}
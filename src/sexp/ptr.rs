@@ -1,13 +1,31 @@
-use std::pin::Pin;
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    sync::{Mutex, OnceLock},
+};
 
 use crate::{prelude::*, DowncastSEXP};
 
 use derive_more::Deref;
 use libR_sys::*;
 
+use super::env::Symbol;
+
+/// A type that can be stored in a [`Ptr`] and downcast to again.
+pub trait PtrType {
+    /// An identifier for this type, combined with [`std::any::type_name`] into the name of the tag symbol.
+    ///
+    /// `type_name` alone is not guaranteed to be unique or to differ between versions of a crate, so this should
+    /// include the crate and its version, e.g. `concat!(env!("CARGO_PKG_NAME"), "@", env!("CARGO_PKG_VERSION"))`.
+    const TYPE_ID: &'static str;
+}
+
 #[derive(Deref)]
 #[repr(transparent)]
 /// A wrapper around an external pointer object.
+///
+/// Downcasting only succeeds for external pointers tagged with [`Ptr::type_tag`] of `W`, as done by [`Ptr::wrap_boxed`]
+/// and [`Ptr::wrap_typed`], that were not finalized yet. Pointers with other tags are checked with [`Ptr::from_tagged`].
 pub struct Ptr<T: HasSEXP, W> {
     #[deref]
     ptr: T,
@@ -32,23 +50,99 @@ impl<T: HasSEXP, W> Ptr<T, W> {
             }
         }
     }
-    /// [`Ptr::wrap`] a [`Pin<Box<T>>`] into an external pointer.
+    /// [`Ptr::wrap`] a [`Box<W>`] into an external pointer tagged with [`Ptr::type_tag`], that drops the box when finalized.
+    ///
+    /// The external pointer's tag identifies `W`, so `tag` is kept alive with `prot` instead:
+    /// [`Ptr::inner_prot`] is the pair `(prot . tag)`.
     pub fn wrap_boxed<U: HasSEXP, V: JustSEXP>(ptr: Box<W>, tag: V, prot: U) -> Self
+    where
+        T: JustSEXP,
+        W: PtrType,
+    {
+        let prot = unsafe { Rf_cons(prot.get_sexp(), tag.get_sexp()) }.protect();
+        Self::wrap_boxed_tagged(ptr, Self::type_tag(), prot.get_sexp())
+    }
+    /// [`Ptr::wrap`] a [`Box<W>`] into an external pointer tagged with [`Ptr::type_tag`], that drops the box when finalized.
+    pub fn wrap_typed<U: HasSEXP>(ptr: Box<W>, prot: U) -> Self
+    where
+        T: JustSEXP,
+        W: PtrType,
+    {
+        Self::wrap_boxed_tagged(ptr, Self::type_tag(), prot)
+    }
+    fn wrap_boxed_tagged<U: HasSEXP, V: JustSEXP>(ptr: Box<W>, tag: V, prot: U) -> Self
     where
         T: JustSEXP,
     {
         unsafe extern "C" fn finalize_boxed<W>(ptr: SEXP) {
             unsafe {
-                drop(Box::<W>::from_raw(R_ExternalPtrAddr(ptr) as *mut W));
+                let addr = R_ExternalPtrAddr(ptr) as *mut W;
+                if !addr.is_null() {
+                    // Clear the address first, so the pointer can't be downcast while or after the box is dropped.
+                    R_ClearExternalPtr(ptr);
+                    drop(Box::<W>::from_raw(addr));
+                }
             }
+            #[cfg(feature = "leak_tracking")]
+            crate::debug::untrack_external_ptr(ptr);
         }
         let ret = Self::wrap(Box::into_raw(ptr).cast(), tag, prot);
 
         ret.register_drop(Some(finalize_boxed::<W>));
+        #[cfg(feature = "leak_tracking")]
+        crate::debug::track_handle(
+            crate::protect::HandleKind::ExternalPtr,
+            ret.get_sexp(),
+            std::any::type_name::<W>(),
+        );
 
         ret
     }
-    /// The SEXP of the inner protected object.
+    /// The tag identifying external pointers to `W`, the symbol named `<TYPE_ID>::<type_name>`
+    /// from [`PtrType::TYPE_ID`] and [`std::any::type_name`].
+    ///
+    /// Pass it to [`Ptr::wrap`] for the pointer to be downcast to `Ptr<_, W>`.
+    pub fn type_tag() -> Symbol<SEXP>
+    where
+        W: PtrType,
+    {
+        // Symbols are never collected, so the installed symbol can be reused.
+        static TAGS: OnceLock<Mutex<HashMap<(&'static str, &'static str), usize>>> =
+            OnceLock::new();
+        let type_name = std::any::type_name::<W>();
+        let mut tags = TAGS.get_or_init(Default::default).lock().unwrap();
+        let sexp = *tags.entry((W::TYPE_ID, type_name)).or_insert_with(|| {
+            Symbol::new(&format!("{}::{}", W::TYPE_ID, type_name)).get_sexp() as usize
+        });
+        unsafe { Symbol::wrap_sexp_unchecked(sexp as SEXP) }
+    }
+    /// Check that `sexp` is an external pointer with the given tag that was not finalized yet.
+    ///
+    /// Tags are compared by identity, so `tag` should be a symbol or the object the pointer was created with.
+    pub fn from_tagged(sexp: T, tag: &impl HasSEXP) -> Option<Self>
+    where
+        T: JustSEXP,
+    {
+        if sexp.sexp_type() != SEXPTYPE::EXTPTRSXP {
+            return None;
+        }
+        // Symbols are interned, so symbol tags are the same object if they have the same name.
+        let (ptr_tag, addr) = unsafe {
+            (
+                R_ExternalPtrTag(sexp.get_sexp()),
+                R_ExternalPtrAddr(sexp.get_sexp()),
+            )
+        };
+        if ptr_tag == tag.get_sexp() && !addr.is_null() {
+            Some(Ptr {
+                ptr: sexp,
+                _marker: std::marker::PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+    /// The SEXP of the inner protected object, a pair of `prot` and `tag` for [`Ptr::wrap_boxed`].
     pub fn inner_prot(&self) -> SEXP {
         unsafe { R_ExternalPtrProtected(self.ptr.get_sexp()) }
     }
@@ -61,8 +155,14 @@ impl<T: HasSEXP, W> Ptr<T, W> {
         unsafe { R_ExternalPtrAddr(self.ptr.get_sexp()).cast() }
     }
     /// The pointer this SEXP wraps as a mutable reference.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pointer was cleared, e.g. because it was finalized.
     pub fn get_ref(&self) -> Pin<&mut W> {
-        unsafe { Pin::new_unchecked(&mut *self.get_ptr()) }
+        let ptr = self.get_ptr();
+        assert!(!ptr.is_null(), "external pointer is NULL");
+        unsafe { Pin::new_unchecked(&mut *ptr) }
     }
     /// Register a finalizer for this external pointer.
     pub fn register_drop(&self, drop: Option<unsafe extern "C" fn(SEXP)>) {
@@ -78,15 +178,8 @@ impl<T: HasSEXP, W> HasSEXP for Ptr<T, W> {
     }
 }
 
-impl<T: JustSEXP, W> DowncastSEXP<Ptr<T, W>> for T {
+impl<T: JustSEXP, W: PtrType> DowncastSEXP<Ptr<T, W>> for T {
     fn downcast(self) -> Option<Ptr<T, W>> {
-        if self.sexp_type() == SEXPTYPE::EXTPTRSXP {
-            Some(Ptr {
-                ptr: self,
-                _marker: std::marker::PhantomData,
-            })
-        } else {
-            None
-        }
+        Ptr::from_tagged(self, &Ptr::<T, W>::type_tag())
     }
 }
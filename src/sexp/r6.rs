@@ -0,0 +1,139 @@
+use std::{ffi::CString, fmt::Debug};
+
+use crate::{prelude::*, protect::Protected, DowncastSEXP, ProtectedSEXP};
+use derive_more::Deref;
+use libR_sys::*;
+
+use super::{
+    env::{Env, EnvError, Symbol},
+    function::Callable,
+    lang::PairlistBuilder,
+};
+
+#[derive(Deref)]
+#[repr(transparent)]
+/// A wrapper around an R6 object.
+///
+/// R6 objects are environments with a class attribute ending in `"R6"`.
+/// Public fields and methods are bindings in the object environment itself,
+/// `self` and `private` live in the `.__enclos_env__` environment that all methods close over.
+pub struct R6Object<T: JustSEXP> {
+    env: Env<T>,
+}
+
+impl<T: JustSEXP + Debug> Debug for R6Object<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("R6Object")
+            .field(self.env.inner_ref())
+            .finish()
+    }
+}
+
+impl<T: JustSEXP> HasSEXP for R6Object<T> {
+    fn get_sexp(&self) -> SEXP {
+        self.env.get_sexp()
+    }
+}
+
+impl<T: JustSEXP> R6Object<T> {
+    /// The class chain of the object, most specific first, ending in `"R6"`.
+    pub fn classes(&self) -> Vec<String> {
+        let class = self.attrib(unsafe { R_ClassSymbol });
+        class
+            .downcast_to::<CharacterVectorSEXP<_>>()
+            .map(|class| {
+                (0..class.len())
+                    .filter_map(|i| class.get_elt(i).as_str().map(ToOwned::to_owned))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Check if the object is an instance of the given class or inherits from it.
+    pub fn inherits(&self, class: &str) -> bool {
+        let cstr = CString::new(class).expect("Failed to convert class to CString");
+        unsafe { Rf_inherits(self.get_sexp(), cstr.as_ptr()) }.into()
+    }
+
+    /// Get the value of a public field, like `obj$name`.
+    ///
+    /// Active bindings are evaluated.
+    pub fn field<S: JustSEXP>(&self, name: Symbol<S>) -> Option<SEXP> {
+        self.env.peek(name)
+    }
+
+    /// Set the value of an existing public field, like `obj$name <- value`.
+    ///
+    /// R6 objects are locked by default, so new fields cannot be added, and methods are locked bindings.
+    /// Those cases return an error instead of raising an R error.
+    pub fn set_field<S: JustSEXP>(
+        &mut self,
+        name: Symbol<S>,
+        value: impl HasSEXP,
+    ) -> Result<(), EnvError> {
+        if !self.env.contains(&name) {
+            return Err(EnvError::Unbound);
+        }
+        self.env.assign(name, value)
+    }
+
+    /// Get a public method.
+    pub fn method<S: JustSEXP>(&self, name: Symbol<S>) -> Option<Callable<SEXP>> {
+        self.env.peek(name)?.downcast_to::<Callable<_>>()
+    }
+
+    /// Start building a call to a public method, like `obj$name(...)`.
+    ///
+    /// The returned builder is used the same way as [`ProtectedSEXP::build_pairlist`], the method is evaluated with its own enclosure.
+    pub fn call_method<S: JustSEXP>(
+        &self,
+        name: Symbol<S>,
+    ) -> Option<PairlistBuilder<Protected<Callable<SEXP>>>> {
+        Some(self.method(name)?.protect().build_pairlist())
+    }
+
+    /// The environment that all methods close over, containing `self`, `private` and `super`.
+    pub fn enclos_env(&self) -> Option<Env<SEXP>> {
+        self.env
+            .peek(Symbol::new(".__enclos_env__"))?
+            .downcast_to::<Env<_>>()
+    }
+
+    /// The environment of private fields and methods, if the class has any.
+    pub fn private(&self) -> Option<Env<SEXP>> {
+        self.enclos_env()?
+            .peek(Symbol::new("private"))?
+            .downcast_to::<Env<_>>()
+    }
+}
+
+unsafe impl<T: JustSEXP> JustSEXP for R6Object<T> {
+    type Inner = Env<T>;
+    fn inner_ref(&self) -> &Self::Inner {
+        &self.env
+    }
+    unsafe fn wrap_sexp_unchecked(sexp: SEXP) -> Self {
+        R6Object {
+            env: Env::wrap_sexp_unchecked(sexp),
+        }
+    }
+    fn wrap_sexp(sexp: SEXP) -> Option<Self> {
+        let r6 = CString::new("R6").unwrap();
+        if unsafe { Rf_inherits(sexp, r6.as_ptr()) }.into() {
+            Some(R6Object {
+                env: Env::wrap_sexp(sexp)?,
+            })
+        } else {
+            None
+        }
+    }
+    fn upcast(self) -> Self::Inner {
+        self.env
+    }
+}
+
+impl<T: JustSEXP> DowncastSEXP<R6Object<T>> for T {
+    fn downcast(self) -> Option<R6Object<T>> {
+        R6Object::wrap_sexp(self.get_sexp())
+    }
+}
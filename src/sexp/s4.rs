@@ -0,0 +1,129 @@
+use std::{
+    ffi::{CStr, CString},
+    fmt::Debug,
+};
+
+use crate::{prelude::*, DowncastSEXP};
+use derive_more::Deref;
+use libR_sys::*;
+
+use super::env::Symbol;
+
+#[derive(Deref)]
+#[repr(transparent)]
+/// A wrapper around an S4 object, either an `S4SXP` or any object with the S4 bit set.
+pub struct S4Object<T: JustSEXP> {
+    sexp: T,
+}
+
+impl<T: JustSEXP + Debug> Debug for S4Object<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("S4Object").field(&self.sexp).finish()
+    }
+}
+
+impl S4Object<SEXP> {
+    /// Create a new instance of a class with default slot values, like `methods::new(class)` without `initialize` arguments.
+    ///
+    /// Returns [`None`] if the class is not defined.
+    pub fn new_cstr(class: &CStr) -> Option<Self> {
+        unsafe {
+            let class_def = R_getClassDef(class.as_ptr());
+            if class_def == R_NilValue {
+                return None;
+            }
+            Rf_protect(class_def);
+            let sexp = R_do_new_object(class_def);
+            Rf_unprotect(1);
+            Some(S4Object { sexp })
+        }
+    }
+
+    /// Create a new instance of a class with default slot values, like `methods::new(class)` without `initialize` arguments.
+    ///
+    /// Returns [`None`] if the class is not defined.
+    pub fn new(class: &str) -> Option<Self> {
+        let cstr = CString::new(class).expect("Failed to convert class to CString");
+        S4Object::new_cstr(cstr.as_c_str())
+    }
+}
+
+impl<T: JustSEXP> HasSEXP for S4Object<T> {
+    fn get_sexp(&self) -> SEXP {
+        self.sexp.get_sexp()
+    }
+}
+
+impl<T: JustSEXP> S4Object<T> {
+    /// Check if the object has the given slot.
+    pub fn has_slot<S: JustSEXP>(&self, name: &Symbol<S>) -> bool {
+        unsafe { R_has_slot(self.get_sexp(), name.get_sexp()) != 0 }
+    }
+
+    /// Get the value of a slot, like `obj@name`.
+    pub fn slot<S: JustSEXP>(&self, name: Symbol<S>) -> Option<SEXP> {
+        if self.has_slot(&name) {
+            Some(unsafe { R_do_slot(self.get_sexp(), name.get_sexp()) })
+        } else {
+            None
+        }
+    }
+
+    /// Set the value of a slot, like `obj@name <- value`.
+    ///
+    /// The value is not checked against the class definition, like `methods::slot(obj, name, check = FALSE) <- value`.
+    pub fn set_slot<S: JustSEXP>(&mut self, name: Symbol<S>, value: impl HasSEXP) {
+        unsafe {
+            R_do_slot_assign(self.get_sexp(), name.get_sexp(), value.get_sexp());
+        }
+    }
+
+    /// Find the first of the given classes this object is an instance of, including through inheritance.
+    ///
+    /// Returns the index of the matching class in `classes`.
+    pub fn class_index(&self, classes: &[&CStr]) -> Option<usize> {
+        let mut valid = classes
+            .iter()
+            .map(|class| class.as_ptr())
+            .chain(std::iter::once(std::ptr::null()))
+            .collect::<Vec<_>>();
+        let index = unsafe { R_check_class_etc(self.get_sexp(), valid.as_mut_ptr()) };
+        usize::try_from(index).ok()
+    }
+
+    /// Check if this object is an instance of the given class, like `methods::is(obj, class)`.
+    pub fn is(&self, class: &str) -> bool {
+        let cstr = CString::new(class).expect("Failed to convert class to CString");
+        self.class_index(&[cstr.as_c_str()]).is_some()
+    }
+}
+
+unsafe impl<T: JustSEXP> JustSEXP for S4Object<T> {
+    type Inner = T;
+    fn inner_ref(&self) -> &Self::Inner {
+        &self.sexp
+    }
+    unsafe fn wrap_sexp_unchecked(sexp: SEXP) -> Self {
+        S4Object {
+            sexp: T::wrap_sexp_unchecked(sexp),
+        }
+    }
+    fn wrap_sexp(sexp: SEXP) -> Option<Self> {
+        if unsafe { IS_S4_OBJECT(sexp) } != 0 {
+            Some(S4Object {
+                sexp: T::wrap_sexp(sexp)?,
+            })
+        } else {
+            None
+        }
+    }
+    fn upcast(self) -> Self::Inner {
+        self.sexp
+    }
+}
+
+impl<T: JustSEXP> DowncastSEXP<S4Object<T>> for T {
+    fn downcast(self) -> Option<S4Object<T>> {
+        S4Object::wrap_sexp(self.get_sexp())
+    }
+}
@@ -0,0 +1,291 @@
+use std::{ffi::CStr, fmt::Debug};
+
+use derive_more::Deref;
+use itertools::Either;
+use libR_sys::{SEXPTYPE::*, *};
+
+use crate::{
+    sexp::{
+        env::Symbol,
+        vector::{IntegerVector, RealVector},
+    },
+    DowncastSEXP, HasSEXP, JustSEXP, ProtectedSEXP,
+};
+
+/// The storage layout of a [`SparseMatrix`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SparseFormat {
+    /// Compressed sparse column, the `dgCMatrix` class.
+    Csc,
+    /// Compressed sparse row, the `dgRMatrix` class.
+    Csr,
+    /// Triplet (coordinate) form, the `dgTMatrix` class.
+    Triplet,
+}
+
+impl SparseFormat {
+    /// The name of the Matrix package class for this format.
+    pub fn class_name(&self) -> &'static CStr {
+        unsafe {
+            CStr::from_bytes_with_nul_unchecked(match self {
+                SparseFormat::Csc => b"dgCMatrix\0",
+                SparseFormat::Csr => b"dgRMatrix\0",
+                SparseFormat::Triplet => b"dgTMatrix\0",
+            })
+        }
+    }
+
+    fn of_sexp(sexp: SEXP) -> Option<Self> {
+        [SparseFormat::Csc, SparseFormat::Csr, SparseFormat::Triplet]
+            .into_iter()
+            .find(|format| unsafe { Rf_inherits(sexp, format.class_name().as_ptr()) }.into())
+    }
+}
+
+fn slot_sexp(sexp: SEXP, name: &str) -> SEXP {
+    unsafe { R_do_slot(sexp, Symbol::new(name).get_sexp()) }
+}
+
+/// Borrow an integer slot.
+///
+/// # Safety
+///
+/// The slot must be an INTSXP and the slice must not outlive the object.
+unsafe fn int_slot<'a>(sexp: SEXP, name: &str) -> &'a [i32] {
+    let slot = slot_sexp(sexp, name);
+    std::slice::from_raw_parts(INTEGER(slot), Rf_xlength(slot) as usize)
+}
+
+#[derive(Deref)]
+#[repr(transparent)]
+/// A wrapper around a double-precision sparse matrix from the Matrix package.
+///
+/// Recognizes the S4 classes `dgCMatrix`, `dgRMatrix` and `dgTMatrix`. All indices are 0-based, as stored by the Matrix package.
+pub struct SparseMatrix<T: JustSEXP> {
+    sexp: T,
+}
+
+impl<T: JustSEXP> Debug for SparseMatrix<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (nrow, ncol) = self.dim();
+        write!(
+            f,
+            "SparseMatrix ({:?}, {}x{}, {} non-zeros)",
+            self.format(),
+            nrow,
+            ncol,
+            self.nnz()
+        )
+    }
+}
+
+impl<T: JustSEXP> HasSEXP for SparseMatrix<T> {
+    fn get_sexp(&self) -> SEXP {
+        self.sexp.get_sexp()
+    }
+}
+
+impl<T: JustSEXP> SparseMatrix<T> {
+    /// The storage layout of this matrix.
+    pub fn format(&self) -> SparseFormat {
+        SparseFormat::of_sexp(self.get_sexp()).expect("not a supported sparse matrix")
+    }
+
+    /// The `Dim` slot, as `(nrow, ncol)`.
+    pub fn dim(&self) -> (usize, usize) {
+        let dim = unsafe { int_slot(self.get_sexp(), "Dim") };
+        (dim[0] as usize, dim[1] as usize)
+    }
+
+    /// The `i` slot: row indices, absent for `dgRMatrix`.
+    pub fn i(&self) -> Option<&[i32]> {
+        match self.format() {
+            SparseFormat::Csr => None,
+            _ => Some(unsafe { int_slot(self.get_sexp(), "i") }),
+        }
+    }
+
+    /// The `j` slot: column indices, absent for `dgCMatrix`.
+    pub fn j(&self) -> Option<&[i32]> {
+        match self.format() {
+            SparseFormat::Csc => None,
+            _ => Some(unsafe { int_slot(self.get_sexp(), "j") }),
+        }
+    }
+
+    /// The `p` slot: column (or row for `dgRMatrix`) pointers, absent for `dgTMatrix`.
+    pub fn p(&self) -> Option<&[i32]> {
+        match self.format() {
+            SparseFormat::Triplet => None,
+            _ => Some(unsafe { int_slot(self.get_sexp(), "p") }),
+        }
+    }
+
+    /// The `x` slot: the stored values.
+    pub fn x(&self) -> &[f64] {
+        unsafe {
+            let slot = slot_sexp(self.get_sexp(), "x");
+            std::slice::from_raw_parts(REAL(slot), Rf_xlength(slot) as usize)
+        }
+    }
+
+    /// The number of stored entries.
+    ///
+    /// For `dgTMatrix` duplicated entries are counted separately, they are summed when the matrix is used.
+    pub fn nnz(&self) -> usize {
+        self.x().len()
+    }
+
+    /// Iterate over the stored entries as `(row, col, value)`.
+    pub fn iter_nonzero(&self) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        let x = self.x();
+        match self.format() {
+            SparseFormat::Triplet => {
+                let (i, j) = (self.i().unwrap(), self.j().unwrap());
+                Either::Right(
+                    i.iter()
+                        .zip(j)
+                        .zip(x)
+                        .map(|((&i, &j), &x)| (i as usize, j as usize, x)),
+                )
+            }
+            format => {
+                let p = self.p().unwrap();
+                let (inner, transpose) = match format {
+                    SparseFormat::Csc => (self.i().unwrap(), false),
+                    _ => (self.j().unwrap(), true),
+                };
+                Either::Left(p.windows(2).enumerate().flat_map(move |(outer, range)| {
+                    (range[0] as usize..range[1] as usize).map(move |k| {
+                        let inner = inner[k] as usize;
+                        if transpose {
+                            (outer, inner, x[k])
+                        } else {
+                            (inner, outer, x[k])
+                        }
+                    })
+                }))
+            }
+        }
+    }
+}
+
+impl SparseMatrix<SEXP> {
+    /// Create a new `dgCMatrix` from compressed sparse column data.
+    ///
+    /// The Matrix package must already be loaded. The data is validated the same way the Matrix package does:
+    /// `p` has `ncol + 1` non-decreasing entries starting at 0 and ending at `nnz`, and row indices are strictly increasing within each column.
+    pub fn new_csc(
+        nrow: usize,
+        ncol: usize,
+        p: &[i32],
+        i: &[i32],
+        x: &[f64],
+    ) -> Result<Self, &'static str> {
+        if p.len() != ncol + 1 {
+            return Err("p must have length ncol + 1");
+        }
+        if i.len() != x.len() {
+            return Err("i and x must have the same length");
+        }
+        if p[0] != 0 || p[ncol] as usize != i.len() {
+            return Err("p must start at 0 and end at the number of non-zeros");
+        }
+        if p.windows(2).any(|w| w[0] > w[1]) {
+            return Err("p must be non-decreasing");
+        }
+        for range in p.windows(2) {
+            let rows = &i[range[0] as usize..range[1] as usize];
+            if rows.iter().any(|&r| r < 0 || r as usize >= nrow) {
+                return Err("row index out of bounds");
+            }
+            if rows.windows(2).any(|w| w[0] >= w[1]) {
+                return Err("row indices must be strictly increasing within a column");
+            }
+        }
+
+        let (Ok(nrow), Ok(ncol)) = (i32::try_from(nrow), i32::try_from(ncol)) else {
+            return Err("dimensions must fit in an R integer");
+        };
+
+        unsafe {
+            let class_def = R_getClassDef(SparseFormat::Csc.class_name().as_ptr());
+            if class_def == R_NilValue {
+                return Err("dgCMatrix class not found, is the Matrix package loaded?");
+            }
+            let obj = R_do_new_object(class_def).protect();
+
+            // Each slot only needs protection until it is assigned to the protected object.
+            {
+                let mut slot_i = IntegerVector::new(i.len()).protect();
+                slot_i.as_mut_slice().copy_from_slice(i);
+                R_do_slot_assign(
+                    obj.get_sexp(),
+                    Symbol::new("i").get_sexp(),
+                    slot_i.get_sexp(),
+                );
+            }
+            {
+                let mut slot_p = IntegerVector::new(p.len()).protect();
+                slot_p.as_mut_slice().copy_from_slice(p);
+                R_do_slot_assign(
+                    obj.get_sexp(),
+                    Symbol::new("p").get_sexp(),
+                    slot_p.get_sexp(),
+                );
+            }
+            {
+                let mut slot_x = RealVector::new(x.len()).protect();
+                slot_x.as_mut_slice().copy_from_slice(x);
+                R_do_slot_assign(
+                    obj.get_sexp(),
+                    Symbol::new("x").get_sexp(),
+                    slot_x.get_sexp(),
+                );
+            }
+            {
+                let mut slot_dim = IntegerVector::new(2).protect();
+                slot_dim.as_mut_slice().copy_from_slice(&[nrow, ncol]);
+                R_do_slot_assign(
+                    obj.get_sexp(),
+                    Symbol::new("Dim").get_sexp(),
+                    slot_dim.get_sexp(),
+                );
+            }
+
+            Ok(SparseMatrix {
+                sexp: obj.unprotect(),
+            })
+        }
+    }
+}
+
+unsafe impl<T: JustSEXP> JustSEXP for SparseMatrix<T> {
+    type Inner = T;
+    fn inner_ref(&self) -> &Self::Inner {
+        &self.sexp
+    }
+    unsafe fn wrap_sexp_unchecked(sexp: SEXP) -> Self {
+        SparseMatrix {
+            sexp: T::wrap_sexp_unchecked(sexp),
+        }
+    }
+    fn wrap_sexp(sexp: SEXP) -> Option<Self> {
+        if unsafe { TYPEOF(sexp) } == S4SXP && SparseFormat::of_sexp(sexp).is_some() {
+            Some(SparseMatrix {
+                sexp: T::wrap_sexp(sexp)?,
+            })
+        } else {
+            None
+        }
+    }
+    fn upcast(self) -> Self::Inner {
+        self.sexp
+    }
+}
+
+impl<T: JustSEXP> DowncastSEXP<SparseMatrix<T>> for T {
+    fn downcast(self) -> Option<SparseMatrix<T>> {
+        SparseMatrix::wrap_sexp(self.get_sexp())
+    }
+}
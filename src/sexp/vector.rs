@@ -68,6 +68,15 @@ macro_rules! impl_simple_vector {
                     std::slice::from_raw_parts($deref_fn(sexp), Rf_xlength(sexp) as usize)
                 }
             }
+
+            #[must_use]
+            /// Get a mutable slice of the elements in this vector.
+            pub fn as_mut_slice(&mut self) -> &mut [$elem_ty] {
+                unsafe {
+                    let sexp = self.inner.get_sexp();
+                    std::slice::from_raw_parts_mut($deref_fn(sexp), Rf_xlength(sexp) as usize)
+                }
+            }
         }
 
         impl<T: HasSEXP> IndexableSEXP for $struct<T> {
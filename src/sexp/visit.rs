@@ -0,0 +1,193 @@
+use crate::{prelude::*, AnySexp};
+use libR_sys::*;
+
+use super::{
+    env::Symbol,
+    lang::{Lang, Pairlist},
+};
+
+/// Callbacks for [`walk`], a read-only traversal of an R syntax tree.
+///
+/// All callbacks do nothing by default.
+pub trait LangVisitor {
+    /// Called for each call before its function and arguments are visited.
+    ///
+    /// Return `false` to skip the children of the call.
+    fn visit_call(&mut self, _call: &Lang<SEXP>) -> bool {
+        true
+    }
+
+    /// Called for each argument of a call or element of a pairlist, with its name, before the value is visited.
+    ///
+    /// Empty arguments, like in `x[, 1]`, have `R_MissingArg` as the value and are not visited further.
+    fn visit_arg(&mut self, _tag: Option<&Symbol<SEXP>>, _value: SEXP) {}
+
+    /// Called for each pairlist that is not a call, such as the formals in `function(x = 1) x`.
+    ///
+    /// Return `false` to skip the elements of the pairlist.
+    fn visit_pairlist(&mut self, _pairlist: &Pairlist<SEXP>) -> bool {
+        true
+    }
+
+    /// Called for each symbol.
+    fn visit_symbol(&mut self, _symbol: &Symbol<SEXP>) {}
+
+    /// Called for everything else, such as literal values or objects spliced into the call.
+    fn visit_constant(&mut self, _value: AnySexp<SEXP>) {}
+}
+
+/// Walk an R syntax tree depth-first, such as a [`Lang`] or an expression vector from `parse`.
+///
+/// The tree must be protected by the caller.
+pub fn walk(visitor: &mut impl LangVisitor, sexp: &impl HasSEXP) {
+    walk_sexp(visitor, sexp.get_sexp());
+}
+
+fn walk_sexp(visitor: &mut impl LangVisitor, sexp: SEXP) {
+    unsafe {
+        match TYPEOF(sexp) {
+            SEXPTYPE::LANGSXP => {
+                if visitor.visit_call(&Lang::wrap_sexp_unchecked(sexp)) {
+                    walk_sexp(visitor, CAR(sexp));
+                    walk_args(visitor, CDR(sexp));
+                }
+            }
+            SEXPTYPE::LISTSXP => {
+                if visitor.visit_pairlist(&Pairlist::wrap_sexp_unchecked(sexp)) {
+                    walk_args(visitor, sexp);
+                }
+            }
+            SEXPTYPE::EXPRSXP => {
+                for i in 0..Rf_xlength(sexp) {
+                    walk_sexp(visitor, VECTOR_ELT(sexp, i));
+                }
+            }
+            SEXPTYPE::SYMSXP if sexp != R_MissingArg => {
+                visitor.visit_symbol(&Symbol::wrap_sexp_unchecked(sexp));
+            }
+            SEXPTYPE::SYMSXP => {}
+            _ => visitor.visit_constant(AnySexp::from(sexp)),
+        }
+    }
+}
+
+fn walk_args(visitor: &mut impl LangVisitor, mut ptr: SEXP) {
+    unsafe {
+        while ptr != R_NilValue {
+            let tag = Symbol::wrap_sexp(TAG(ptr));
+            visitor.visit_arg(tag.as_ref(), CAR(ptr));
+            walk_sexp(visitor, CAR(ptr));
+            ptr = CDR(ptr);
+        }
+    }
+}
+
+/// Callbacks for [`rewrite`], a traversal of an R syntax tree that can replace sub-expressions.
+///
+/// Each callback returns the replacement, or [`None`] to keep the node.
+/// Replacements do not need to be protected, and are not visited again.
+pub trait LangRewriter {
+    /// Called for each call before its function and arguments are rewritten.
+    ///
+    /// The children are only rewritten if the call is kept.
+    fn rewrite_call(&mut self, _call: &Lang<SEXP>) -> Option<SEXP> {
+        None
+    }
+
+    /// Called for each symbol.
+    fn rewrite_symbol(&mut self, _symbol: &Symbol<SEXP>) -> Option<SEXP> {
+        None
+    }
+
+    /// Called for everything else that is not a call, pairlist, expression vector or symbol.
+    fn rewrite_constant(&mut self, _value: AnySexp<SEXP>) -> Option<SEXP> {
+        None
+    }
+}
+
+/// Rewrite an R syntax tree depth-first, such as a [`Lang`] or an expression vector from `parse`.
+///
+/// The original tree is never modified, since code is often shared (e.g. with function bodies):
+/// calls, pairlists and expression vectors with rewritten children are copied, the rest is shared with the original.
+/// The tree must be protected by the caller, the result is not protected.
+pub fn rewrite(rewriter: &mut impl LangRewriter, sexp: &impl HasSEXP) -> SEXP {
+    rewrite_sexp(rewriter, sexp.get_sexp())
+}
+
+fn rewrite_sexp(rewriter: &mut impl LangRewriter, sexp: SEXP) -> SEXP {
+    unsafe {
+        match TYPEOF(sexp) {
+            SEXPTYPE::LANGSXP => match rewriter.rewrite_call(&Lang::wrap_sexp_unchecked(sexp)) {
+                Some(replacement) => replacement,
+                None => rewrite_list(rewriter, sexp),
+            },
+            SEXPTYPE::LISTSXP => rewrite_list(rewriter, sexp),
+            SEXPTYPE::EXPRSXP => rewrite_expression(rewriter, sexp),
+            SEXPTYPE::SYMSXP if sexp != R_MissingArg => rewriter
+                .rewrite_symbol(&Symbol::wrap_sexp_unchecked(sexp))
+                .unwrap_or(sexp),
+            SEXPTYPE::SYMSXP => sexp,
+            _ => rewriter
+                .rewrite_constant(AnySexp::from(sexp))
+                .unwrap_or(sexp),
+        }
+    }
+}
+
+/// Rewrite the values of a call or pairlist, copying the spine on the first change.
+fn rewrite_list(rewriter: &mut impl LangRewriter, sexp: SEXP) -> SEXP {
+    unsafe {
+        // Holds the copy in its CAR once it exists, so it stays protected without reordering the protect stack.
+        let holder = Rf_cons(R_NilValue, R_NilValue).protect();
+        let mut index = 0;
+        let mut ptr = sexp;
+        while ptr != R_NilValue {
+            let old = CAR(ptr);
+            let new = rewrite_sexp(rewriter, old);
+            if new != old {
+                let new = new.protect();
+                if CAR(holder.get_sexp()) == R_NilValue {
+                    SETCAR(holder.get_sexp(), Rf_shallow_duplicate(sexp));
+                }
+                let mut cell = CAR(holder.get_sexp());
+                for _ in 0..index {
+                    cell = CDR(cell);
+                }
+                SETCAR(cell, new.get_sexp());
+            }
+            index += 1;
+            ptr = CDR(ptr);
+        }
+        let copy = CAR(holder.get_sexp());
+        if copy == R_NilValue {
+            sexp
+        } else {
+            copy
+        }
+    }
+}
+
+/// Rewrite the elements of an expression vector, copying it on the first change.
+fn rewrite_expression(rewriter: &mut impl LangRewriter, sexp: SEXP) -> SEXP {
+    unsafe {
+        // Holds the copy in its CAR once it exists, see `rewrite_list`.
+        let holder = Rf_cons(R_NilValue, R_NilValue).protect();
+        for i in 0..Rf_xlength(sexp) {
+            let old = VECTOR_ELT(sexp, i);
+            let new = rewrite_sexp(rewriter, old);
+            if new != old {
+                let new = new.protect();
+                if CAR(holder.get_sexp()) == R_NilValue {
+                    SETCAR(holder.get_sexp(), Rf_shallow_duplicate(sexp));
+                }
+                SET_VECTOR_ELT(CAR(holder.get_sexp()), i, new.get_sexp());
+            }
+        }
+        let copy = CAR(holder.get_sexp());
+        if copy == R_NilValue {
+            sexp
+        } else {
+            copy
+        }
+    }
+}
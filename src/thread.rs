@@ -0,0 +1,242 @@
+//! Guards against calling R from threads other than the R main thread, and running work on other threads.
+//!
+//! R is single-threaded, calling any R API from another thread corrupts the interpreter.
+//! All wrapper types hold raw [`SEXP`](libR_sys::SEXP) pointers and so are neither [`Send`] nor [`Sync`],
+//! and in debug builds operations on them panic when used from another thread.
+//!
+//! Work that doesn't need R can run on other threads with [`spawn_blocking`],
+//! which lets those threads send code to run on the R main thread while it waits for them.
+use std::{
+    any::Any,
+    fmt::Display,
+    marker::PhantomData,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, OnceLock,
+    },
+    thread::{self, ThreadId},
+    time::Duration,
+};
+
+use crate::{
+    convert::{named, FromSexp, IntoSexp},
+    interrupt::check_interrupt,
+    prelude::*,
+    sexp::{function::CallError, namespace::call_in_namespace},
+};
+
+static MAIN_THREAD: OnceLock<ThreadId> = OnceLock::new();
+
+/// A token proving that the current thread is the R main thread.
+///
+/// The token is neither [`Send`] nor [`Sync`], so it can't leave the thread it was created on.
+#[derive(Debug, Clone, Copy)]
+pub struct MainThread {
+    _marker: PhantomData<*mut ()>,
+}
+
+impl MainThread {
+    /// Record the current thread as the R main thread.
+    ///
+    /// This is done by [`EmbeddedR::init`](crate::embedded::EmbeddedR::init),
+    /// extensions must do it in their `R_init_<pkg>` routine or at the start of `.Call` entry points.
+    /// Registering the same thread again returns a token.
+    ///
+    /// # Panics
+    ///
+    /// Panics if another thread was already recorded.
+    #[track_caller]
+    pub fn register() -> Self {
+        let id = thread::current().id();
+        let main = *MAIN_THREAD.get_or_init(|| id);
+        if main != id {
+            panic!(
+                "cannot register {:?} as the R main thread, {:?} already is",
+                id, main
+            );
+        }
+        MainThread {
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get a token if the current thread is the registered R main thread.
+    ///
+    /// Returns [`None`] if no thread was registered with [`MainThread::register`] yet.
+    pub fn current() -> Option<Self> {
+        (MAIN_THREAD.get() == Some(&thread::current().id())).then_some(MainThread {
+            _marker: PhantomData,
+        })
+    }
+}
+
+/// Check if the current thread is the registered R main thread, `false` if no thread was registered yet.
+pub fn is_main_thread() -> bool {
+    MainThread::current().is_some()
+}
+
+/// Panic if the current thread is not the R main thread, in debug builds only.
+#[track_caller]
+#[inline]
+pub(crate) fn debug_check_main_thread() {
+    #[cfg(debug_assertions)]
+    if !is_main_thread() {
+        match MAIN_THREAD.get() {
+            Some(main) => panic!(
+                "R API called from {:?}, but R runs on {:?}: R objects must only be used on the R main thread",
+                thread::current().id(),
+                main
+            ),
+            None => panic!(
+                "R API called before the R main thread was registered, call MainThread::register first"
+            ),
+        }
+    }
+}
+
+/// A closure sent by a worker thread to be run on the R main thread.
+type Job = Box<dyn FnOnce(MainThread) + Send>;
+
+/// A handle for worker threads to run code on the R main thread, created by [`spawn_blocking`].
+///
+/// Requests are only serviced while the main thread waits in [`JoinHandle::join`].
+#[derive(Clone)]
+pub struct RHandle {
+    jobs: Sender<Job>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl RHandle {
+    /// Run a closure on the R main thread and wait for its result.
+    ///
+    /// Returns [`None`] if the main thread stopped servicing requests, e.g. because the user interrupted it.
+    pub fn run<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(MainThread) -> T + Send + 'static,
+    ) -> Option<T> {
+        let (reply, result) = mpsc::channel();
+        self.jobs
+            .send(Box::new(move |token| {
+                let _ = reply.send(f(token));
+            }))
+            .ok()?;
+        result.recv().ok()
+    }
+
+    /// Parse and evaluate R code in the global environment on the R main thread, like `eval(parse(text = code))`.
+    pub fn eval<T: FromSexp + Send + 'static>(&self, code: &str) -> Result<T, CallError> {
+        let code = code.to_string();
+        self.run(move |_| {
+            let parsed: SEXP = call_in_namespace("base", "parse", (named("text", code.as_str()),))?;
+            let parsed = parsed.protect();
+            let result: SEXP = call_in_namespace("base", "eval", (&parsed,))?;
+            T::from_sexp(result).ok_or(CallError::Conversion(std::any::type_name::<T>()))
+        })
+        .unwrap_or(Err(CallError::Eval(Some(
+            "the R main thread stopped servicing requests".to_string(),
+        ))))
+    }
+
+    /// Check if the main thread gave up waiting, so the work can be abandoned.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Errors from waiting for a worker thread with [`JoinHandle::join`].
+#[derive(Debug)]
+pub enum JoinError {
+    /// The user interrupted R while waiting, the worker was told to cancel with [`RHandle::is_cancelled`].
+    Interrupted,
+    /// The worker thread panicked, with the panic payload.
+    Panicked(Box<dyn Any + Send>),
+}
+
+impl Display for JoinError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JoinError::Interrupted => write!(f, "interrupted by the user"),
+            JoinError::Panicked(_) => write!(f, "worker thread panicked"),
+        }
+    }
+}
+
+impl std::error::Error for JoinError {}
+
+/// A worker thread started with [`spawn_blocking`], joined on the R main thread.
+pub struct JoinHandle<T> {
+    thread: thread::JoinHandle<T>,
+    jobs: Receiver<Job>,
+    cancelled: Arc<AtomicBool>,
+    main_thread: MainThread,
+}
+
+impl<T> JoinHandle<T> {
+    /// Wait for the worker to finish, running its requests for R on this thread.
+    ///
+    /// User interrupts are checked every `interval`.
+    pub fn join_with_interval(self, interval: Duration) -> Result<T, JoinError> {
+        loop {
+            match self.jobs.recv_timeout(interval) {
+                Ok(job) => job(self.main_thread),
+                // Handles may outlive the worker, e.g. in a thread pool.
+                Err(RecvTimeoutError::Timeout) if self.thread.is_finished() => break,
+                Err(RecvTimeoutError::Timeout) => {}
+                // All handles are gone, so the worker can't make requests anymore.
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if check_interrupt().is_err() {
+                self.cancelled.store(true, Ordering::Relaxed);
+                return Err(JoinError::Interrupted);
+            }
+        }
+        self.thread.join().map_err(JoinError::Panicked)
+    }
+
+    /// Wait for the worker to finish, running its requests for R on this thread.
+    ///
+    /// User interrupts are checked every 100ms.
+    pub fn join(self) -> Result<T, JoinError> {
+        self.join_with_interval(Duration::from_millis(100))
+    }
+}
+
+impl<T: IntoSexp> JoinHandle<T> {
+    /// Wait for the worker to finish and convert its result to an R object on this thread.
+    ///
+    /// The returned object is not protected.
+    pub fn join_sexp(self) -> Result<SEXP, JoinError> {
+        self.join().map(IntoSexp::into_sexp)
+    }
+}
+
+/// Run a closure on a new thread, while the R main thread services its requests to use R.
+///
+/// The closure gets an [`RHandle`] to run code on the R main thread, which can be cloned and sent to other threads
+/// (e.g. into a rayon pool). The result is collected with [`JoinHandle::join`] on the R main thread.
+///
+/// # Panics
+///
+/// Panics if called from a thread other than the R main thread.
+#[track_caller]
+pub fn spawn_blocking<T, F>(f: F) -> JoinHandle<T>
+where
+    F: FnOnce(RHandle) -> T + Send + 'static,
+    T: Send + 'static,
+{
+    let main_thread =
+        MainThread::current().expect("spawn_blocking must be called on the R main thread");
+    let (sender, jobs) = mpsc::channel();
+    let cancelled = Arc::new(AtomicBool::new(false));
+    let handle = RHandle {
+        jobs: sender,
+        cancelled: cancelled.clone(),
+    };
+    JoinHandle {
+        thread: thread::spawn(move || f(handle)),
+        jobs,
+        cancelled,
+        main_thread,
+    }
+}
@@ -0,0 +1,218 @@
+//! GC torture testing for code using R objects, to catch missing protection in tests.
+//!
+//! Under `gctorture(TRUE)` R collects garbage on every allocation, so an unprotected object
+//! is collected as soon as anything else is allocated. Use with an [`EmbeddedR`](crate::embedded::EmbeddedR) instance in `#[test]` functions.
+//!
+//! Whether objects registered with [`TortureContext::track`] were collected can only be checked in R built with
+//! `--enable-strict-barrier`, so tracking is only available with [`gc_torture_strict`].
+use std::fmt::Debug;
+
+use libR_sys::*;
+
+use crate::{
+    convert::named,
+    prelude::*,
+    sexp::{function::CallError, namespace::call_in_namespace},
+};
+
+/// Context passed to the closure run by [`gc_torture_strict`].
+pub struct TortureContext {
+    tracked: Vec<(SEXP, String)>,
+}
+
+impl TortureContext {
+    /// Track an object that must stay alive until the end of the run, e.g. an intermediate result.
+    ///
+    /// If it is collected, the `label` is reported in [`TortureError::Collected`].
+    pub fn track(&mut self, sexp: &impl HasSEXP, label: &str) {
+        self.tracked.push((sexp.get_sexp(), label.to_string()));
+    }
+
+    /// The labels of tracked objects that were collected.
+    ///
+    /// # Safety
+    /// R must be built with `--enable-strict-barrier`, otherwise collected objects are not marked and their memory may be reused.
+    unsafe fn collected(&self) -> Vec<String> {
+        self.tracked
+            .iter()
+            .filter(|(sexp, _)| unsafe { TYPEOF(*sexp) } == SEXPTYPE::FREESXP)
+            .map(|(_, label)| label.clone())
+            .collect()
+    }
+}
+
+/// A protection bug found by [`gc_torture`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TortureError<T> {
+    /// Tracked objects were collected while still in use.
+    Collected {
+        /// The run, counting from 1, 0 is the run without torture.
+        run: usize,
+        /// The labels of the collected objects.
+        labels: Vec<String>,
+    },
+    /// Fewer or more values were protected through [`Protected`](crate::protect::Protected) after the run than before.
+    ///
+    /// Only detected with the `checked_protect_stack` feature, which counts the protected values.
+    /// Raw `Rf_protect` calls are never counted.
+    Unbalanced {
+        /// The run, counting from 1, 0 is the run without torture.
+        run: usize,
+        /// The number of protected values before the run.
+        before: usize,
+        /// The number of protected values after the run.
+        after: usize,
+    },
+    /// The output under torture differs from the run without torture.
+    Mismatch {
+        /// The run, counting from 1.
+        run: usize,
+        /// The output of the run without torture.
+        expected: T,
+        /// The output under torture.
+        actual: T,
+    },
+}
+
+impl<T: Debug> std::fmt::Display for TortureError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TortureError::Collected { run, labels } => {
+                write!(
+                    f,
+                    "run {}: collected while in use: {}",
+                    run,
+                    labels.join(", ")
+                )
+            }
+            TortureError::Unbalanced { run, before, after } => write!(
+                f,
+                "run {}: {} values were protected before and {} after",
+                run, before, after
+            ),
+            TortureError::Mismatch {
+                run,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "run {}: expected {:?} but got {:?} under GC torture",
+                run, expected, actual
+            ),
+        }
+    }
+}
+
+impl<T: Debug> std::error::Error for TortureError<T> {}
+
+/// Set GC torture with `gctorture2`, `step` 0 disables it.
+///
+/// `R_gc_torture` is internal to R, so this goes through the R function.
+fn set_gc_torture(step: i32, inhibit_release: bool) -> Result<(), CallError> {
+    call_in_namespace(
+        "base",
+        "gctorture2",
+        (
+            step,
+            named("wait", step),
+            named("inhibit.release", inhibit_release),
+        ),
+    )
+}
+
+/// Disables GC torture when dropped, even if the closure panics.
+struct TortureGuard;
+
+impl TortureGuard {
+    fn enable(inhibit_release: bool) -> Self {
+        set_gc_torture(1, inhibit_release).expect("failed to enable GC torture");
+        TortureGuard
+    }
+}
+
+impl Drop for TortureGuard {
+    fn drop(&mut self) {
+        let _ = set_gc_torture(0, false);
+    }
+}
+
+/// Run a closure once normally and then `runs` times under GC torture, checking for protection bugs.
+///
+/// The output must equal that of the run without torture, so a missing protection shows up as a [`TortureError::Mismatch`]
+/// when a collected object is overwritten by a later allocation. The output should be a Rust value,
+/// since R objects returned from the closure are no longer protected.
+///
+/// With the `checked_protect_stack` feature, the number of values protected through [`Protected`](crate::protect::Protected)
+/// must also be back at its initial value after every run. Without it, imbalances are not detected.
+///
+/// Returns the output of the run without torture.
+pub fn gc_torture<T, F>(runs: usize, mut f: F) -> Result<T, TortureError<T>>
+where
+    T: PartialEq + Debug,
+    F: FnMut() -> T,
+{
+    // Safety: nothing is tracked, so no collected object is inspected.
+    unsafe { run_torture(runs, false, |_| f()) }
+}
+
+/// Like [`gc_torture`], but the closure can [`track`](TortureContext::track) objects, which are checked to not be collected.
+///
+/// # Safety
+/// R must be built with `--enable-strict-barrier`. Only then are collected objects marked as `FREESXP` and kept
+/// instead of being reused, otherwise inspecting a collected object reads freed memory.
+pub unsafe fn gc_torture_strict<T, F>(runs: usize, f: F) -> Result<T, TortureError<T>>
+where
+    T: PartialEq + Debug,
+    F: FnMut(&mut TortureContext) -> T,
+{
+    run_torture(runs, true, f)
+}
+
+/// # Safety
+/// With `strict`, R must be built with `--enable-strict-barrier`.
+unsafe fn run_torture<T, F>(runs: usize, strict: bool, mut f: F) -> Result<T, TortureError<T>>
+where
+    T: PartialEq + Debug,
+    F: FnMut(&mut TortureContext) -> T,
+{
+    let mut run_once = |run: usize| {
+        #[cfg(feature = "checked_protect_stack")]
+        let before = crate::debug::protect_stack_len();
+        let mut context = TortureContext {
+            tracked: Vec::new(),
+        };
+        let output = f(&mut context);
+        if strict {
+            let collected = context.collected();
+            if !collected.is_empty() {
+                return Err(TortureError::Collected {
+                    run,
+                    labels: collected,
+                });
+            }
+        }
+        #[cfg(feature = "checked_protect_stack")]
+        {
+            let after = crate::debug::protect_stack_len();
+            if after != before {
+                return Err(TortureError::Unbalanced { run, before, after });
+            }
+        }
+        Ok(output)
+    };
+
+    let expected = run_once(0)?;
+    // With a strict barrier build, collected objects are not reused, but marked as FREESXP so premature collection can be detected.
+    let _guard = TortureGuard::enable(strict);
+    for run in 1..=runs {
+        let actual = run_once(run)?;
+        if actual != expected {
+            return Err(TortureError::Mismatch {
+                run,
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(expected)
+}
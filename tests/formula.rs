@@ -0,0 +1,49 @@
+//! Compare `Formula::terms` and `Formula::variables` with R's `terms` and `all.vars`.
+use typed_sexp::{
+    convert::FromSexp,
+    embedded::EmbeddedR,
+    prelude::*,
+    sexp::{env::Symbol, formula::Formula, namespace::call_in_namespace},
+};
+
+/// Check the terms, intercept and variables of the formula `code` against R.
+fn check(code: &str) {
+    let formula = call_in_namespace::<SEXP>("stats", "as.formula", (code,))
+        .unwrap()
+        .protect();
+    let parsed = formula
+        .get_sexp()
+        .downcast_to::<Formula<_>>()
+        .expect("as.formula returns a formula");
+
+    let r_terms = call_in_namespace::<SEXP>("stats", "terms", (&formula,))
+        .unwrap()
+        .protect();
+    let labels =
+        Vec::<String>::from_sexp(r_terms.attrib(Symbol::new("term.labels").get_sexp())).unwrap();
+    let intercept =
+        i32::from_sexp(r_terms.attrib(Symbol::new("intercept").get_sexp())).unwrap() != 0;
+    let variables = call_in_namespace::<Vec<String>>("base", "all.vars", (&formula,)).unwrap();
+
+    let terms = parsed.terms().unwrap();
+    let ours = terms
+        .terms
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(ours, labels, "term labels of {}", code);
+    assert_eq!(terms.intercept, intercept, "intercept of {}", code);
+    assert_eq!(parsed.variables(), variables, "variables of {}", code);
+}
+
+#[test]
+fn terms_match_r() {
+    let _r = unsafe { EmbeddedR::init() };
+
+    check("y ~ a*b - a");
+    check("~ (a+b+c)^2 - 1");
+    check("y ~ x + log(z)");
+    check("y ~ x + 0");
+    check("y ~ a:b + b + a");
+    check("y ~ x + x");
+}
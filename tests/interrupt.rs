@@ -0,0 +1,23 @@
+//! A pending interrupt is reported by `check_interrupt` instead of jumping to the top level.
+//!
+//! `R_interrupts_pending` is what R's `SIGINT` handler sets on Unix, so setting it simulates Ctrl-C.
+#![cfg(unix)]
+use libR_sys::R_interrupts_pending;
+use typed_sexp::{
+    embedded::EmbeddedR,
+    interrupt::{check_interrupt, Interrupted},
+};
+
+#[test]
+fn pending_interrupt_is_an_error() {
+    let _r = unsafe { EmbeddedR::init() };
+
+    assert_eq!(check_interrupt(), Ok(()));
+
+    unsafe { R_interrupts_pending = 1 };
+    assert_eq!(check_interrupt(), Err(Interrupted));
+
+    // The interrupt was consumed, and R is usable again.
+    assert_eq!(unsafe { R_interrupts_pending }, 0);
+    assert_eq!(check_interrupt(), Ok(()));
+}
@@ -0,0 +1,50 @@
+//! Round-trip calls built with `PairlistBuilder` through R's argument matching.
+//!
+//! R can only be initialized once per process and must be used from the main thread, so all checks run in one test.
+use typed_sexp::{
+    embedded::EmbeddedR,
+    prelude::*,
+    sexp::{
+        env::Env, function::CallError, lang::DuplicateTag, namespace::get_function,
+        vector::CharacterVector,
+    },
+};
+
+/// Call `base::trimws`, which resolves its `which` argument with `match.arg`.
+fn trimws(args: &[(&str, &str)]) -> Result<String, CallError> {
+    let mut builder = get_function("base", "trimws")?.protect().build_pairlist();
+    for (tag, value) in args {
+        builder = builder.push_tagged(*tag, CharacterVector::scalar(value).protect());
+    }
+    let result = builder
+        .build_lang()
+        .try_eval(Env::global())
+        .map_err(CallError::Eval)?;
+    let result = result
+        .downcast_to::<CharacterVector<_>>()
+        .expect("trimws returns a character vector");
+    Ok(result.get_elt(0).as_str().unwrap().to_string())
+}
+
+#[test]
+fn pairlist_round_trip() {
+    let _r = unsafe { EmbeddedR::init() };
+
+    // Tags are matched by name regardless of their order, and `match.arg` completes a partial value.
+    assert_eq!(trimws(&[("which", "l"), ("x", "  a  ")]).unwrap(), "a  ");
+    assert_eq!(
+        trimws(&[("x", "  a  "), ("which", "right")]).unwrap(),
+        "  a"
+    );
+    // A value that matches none of the choices is an error from `match.arg`.
+    assert!(trimws(&[("x", "  a  "), ("which", "middle")]).is_err());
+
+    let duplicate = get_function("base", "trimws")
+        .unwrap()
+        .protect()
+        .build_pairlist()
+        .push_tagged("x", CharacterVector::scalar("a").protect())
+        .push_tagged("x", CharacterVector::scalar("b").protect())
+        .try_build_lang();
+    assert_eq!(duplicate.err(), Some(DuplicateTag("x".to_string())));
+}
@@ -0,0 +1,51 @@
+//! Rewriting a nested call under `gctorture`, where any unprotected intermediate object is collected.
+use typed_sexp::{
+    embedded::EmbeddedR,
+    prelude::*,
+    sexp::{
+        env::Symbol,
+        expr::{call, sym, Expr},
+        namespace::call_in_namespace,
+        vector::RealVector,
+        visit::{rewrite, LangRewriter},
+    },
+    DEPARSE_DEFAULT_OPTIONS, DEPARSE_DEFAULT_WIDTH,
+};
+
+/// Replaces `x` with a freshly allocated, unprotected `2`.
+struct ReplaceX;
+
+impl LangRewriter for ReplaceX {
+    fn rewrite_symbol(&mut self, symbol: &Symbol<SEXP>) -> Option<SEXP> {
+        (symbol.name() == "x").then(|| RealVector::scalar(2.0).get_sexp())
+    }
+}
+
+fn deparse(sexp: &impl HasSEXP) -> String {
+    sexp.deparse(DEPARSE_DEFAULT_WIDTH, DEPARSE_DEFAULT_OPTIONS)
+        .unwrap()
+        .join("\n")
+}
+
+#[test]
+fn rewrite_under_gc_torture() {
+    let _r = unsafe { EmbeddedR::init() };
+
+    let original = call(
+        sym("f"),
+        [
+            call(sym("g"), [sym("x"), sym("y")]),
+            call(sym("+"), [sym("x"), Expr::Real(1.0)]),
+        ],
+    )
+    .to_sexp()
+    .protect();
+    assert_eq!(deparse(&original), "f(g(x, y), x + 1)");
+
+    call_in_namespace::<()>("base", "gctorture", (true,)).unwrap();
+    let rewritten = rewrite(&mut ReplaceX, &original).protect();
+    call_in_namespace::<()>("base", "gctorture", (false,)).unwrap();
+
+    assert_eq!(deparse(&rewritten), "f(g(2, y), 2 + 1)");
+    assert_eq!(deparse(&original), "f(g(x, y), x + 1)");
+}
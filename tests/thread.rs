@@ -0,0 +1,36 @@
+//! Worker threads started with `spawn_blocking` using R through their `RHandle`.
+use typed_sexp::{
+    convert::FromSexp,
+    embedded::EmbeddedR,
+    prelude::*,
+    sexp::namespace::call_in_namespace,
+    thread::{is_main_thread, spawn_blocking, JoinError},
+};
+
+#[test]
+fn worker_requests_run_on_main_thread() {
+    let _r = unsafe { EmbeddedR::init() };
+
+    let worker = spawn_blocking(|r| {
+        assert!(!is_main_thread());
+        let sum: f64 = r.eval("1 + 1").unwrap();
+        let (on_main, chars) = r
+            .run(|_| {
+                let chars = call_in_namespace::<i32>("base", "nchar", ("abc",)).unwrap();
+                (is_main_thread(), chars)
+            })
+            .unwrap();
+        assert!(on_main);
+        sum + chars as f64
+    });
+    let result = worker.join_sexp().unwrap().protect();
+    assert_eq!(f64::from_sexp(result.get_sexp()), Some(5.0));
+
+    let worker = spawn_blocking(|_| -> f64 { panic!("worker failed") });
+    match worker.join() {
+        Err(JoinError::Panicked(payload)) => {
+            assert_eq!(payload.downcast_ref::<&str>(), Some(&"worker failed"))
+        }
+        other => panic!("expected a panic, got {:?}", other.map(|_| ())),
+    }
+}
@@ -0,0 +1,38 @@
+//! `gc_torture` catches a value that is used after a later allocation without being protected.
+//!
+//! Under torture, allocating the second scalar collects the first one, and R hands its freed node to the second.
+use typed_sexp::{
+    embedded::EmbeddedR,
+    prelude::*,
+    sexp::vector::RealVector,
+    torture::{gc_torture, TortureError},
+};
+
+#[test]
+fn missing_protect_is_caught() {
+    let _r = unsafe { EmbeddedR::init() };
+
+    let unprotected = gc_torture(3, || {
+        let a = RealVector::scalar(1.5);
+        let b = RealVector::scalar(2.5);
+        (a.as_slice()[0], b.as_slice()[0])
+    });
+    assert!(
+        matches!(
+            unprotected,
+            Err(TortureError::Mismatch {
+                expected: (1.5, 2.5),
+                ..
+            })
+        ),
+        "{:?}",
+        unprotected
+    );
+
+    let protected = gc_torture(3, || {
+        let a = RealVector::scalar(1.5).protect();
+        let b = RealVector::scalar(2.5).protect();
+        (a.as_slice()[0], b.as_slice()[0])
+    });
+    assert_eq!(protected, Ok((1.5, 2.5)));
+}